@@ -1,22 +1,62 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::collections::{HashMap, HashSet};
-use crate::game::commons::{GameResult, Code, Board, Turn};
+use std::cell::{Ref, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use crate::game::board_n::BoardN;
+use crate::game::commons::{GameResult, Code, Board, Turn, Key, DecodeError};
+use crate::game::dense_table::{DenseTable, DenseTableError};
+use crate::game::game_graph::{Edge, GameGraph, NodeIndex};
+use crate::game::rule_set::RuleSet;
+use crate::game::tablebase::{Tablebase, TablebaseError};
 use std::marker::PhantomData;
-use serde::ser::{Serialize, Serializer, SerializeStruct};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::{Deserialize, Serialize};
+use serde::ser::{Serializer, SerializeStruct, SerializeMap, SerializeSeq};
 
-#[derive(Debug)]
-pub struct AnalysisTreeNode {
-    game_result: GameResult,
-    next_boards: Rc<RefCell<Vec<Code>>>,
+// keys are turned into codes only when exported
+struct CodedGraph<'a, B: Board>(&'a GameGraph, PhantomData<fn() -> B>);
+
+struct CodedNode<'a, B: Board>(&'a GameGraph, NodeIndex, PhantomData<fn() -> B>);
+
+struct CodedEdge<'a, B: Board>(&'a GameGraph, &'a Edge, PhantomData<fn() -> B>);
+
+impl<'a, B> Serialize for CodedGraph<'a, B> where B: Board {
+    fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error> where
+        S: Serializer {
+        let mut state = serializer.serialize_map(Some(self.0.len()))?;
+        for (index, node) in self.0.get_nodes().iter().enumerate() {
+            state.serialize_entry(&Analyzer::<B>::get_code(&node.key), &CodedNode::<B>(self.0, index as NodeIndex, PhantomData))?;
+        }
+        state.end()
+    }
+}
+
+impl<'a, B> Serialize for CodedNode<'a, B> where B: Board {
+    fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error> where
+        S: Serializer {
+        let node = self.0.get_node(self.1);
+        let mut state = serializer.serialize_struct("Node", 3)?;
+        state.serialize_field("result", &node.game_result)?;
+        state.serialize_field("distance", &node.distance)?;
+        state.serialize_field("next", &self.0.get_edges(self.1).iter().map(|e| CodedEdge::<B>(self.0, e, PhantomData)).collect::<Vec<_>>())?;
+        state.end()
+    }
 }
 
-impl Serialize for AnalysisTreeNode {
+impl<'a, B> Serialize for CodedEdge<'a, B> where B: Board {
     fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error> where
         S: Serializer {
-        let mut state = serializer.serialize_struct("AnalysisTreeNode", 2)?;
-        state.serialize_field("result", &self.game_result)?;
-        state.serialize_field("next", &self.next_boards.borrow().to_vec())?;
+        let mut state = serializer.serialize_struct("Edge", 4)?;
+        state.serialize_field("piece", &self.1.piece_index)?;
+        state.serialize_field("jumps", &self.1.get_jumped_pieces())?;
+        state.serialize_field("board", &Analyzer::<B>::get_code(&self.0.get_node(self.1.next_board).key))?;
+        if self.1.colour_swapped {
+            state.serialize_field("swapped", &true)?;
+        } else {
+            state.skip_field("swapped")?;
+        }
         state.end()
     }
 }
@@ -38,6 +78,170 @@ impl GameResult {
             GameResult::Invalid => "invalid",
         }
     }
+
+    fn from_string(s: &str) -> Option<GameResult> {
+        match s {
+            "unknown" => Some(GameResult::Unknown),
+            "red" => Some(GameResult::RedWins),
+            "yellow" => Some(GameResult::YellowWins),
+            "undeterminable" => Some(GameResult::Undeterminable),
+            "invalid" => Some(GameResult::Invalid),
+            _ => None
+        }
+    }
+}
+
+// the node and the edge of `emit_map_as_json`
+#[derive(Deserialize)]
+struct JsonNode {
+    result: String,
+    distance: Option<u32>,
+    next: Vec<JsonEdge>,
+}
+
+#[derive(Deserialize)]
+struct JsonEdge {
+    piece: usize,
+    jumps: Vec<usize>,
+    board: String,
+    #[serde(default)]
+    swapped: bool,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Json(serde_json::Error),
+    Tablebase(TablebaseError),
+    Decode(DecodeError),
+    // (code of the board, what is different from the rules)
+    Inconsistent(String, String),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(self: &Self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoadError::Json(e) => write!(f, "{}", e),
+            LoadError::Tablebase(e) => write!(f, "{}", e),
+            LoadError::Decode(e) => write!(f, "{}", e),
+            LoadError::Inconsistent(code, s) => write!(f, "{} of {} is inconsistent with the rules", s, code),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Where and how often a long analysis saves its progress, to be resumed by `Analyzer::resume`.
+pub struct Checkpoint {
+    pub path: PathBuf,
+    // boards expanded in the search, or decided in the solving, between the saves
+    pub interval: usize,
+    // set e.g. on Ctrl-C, to save and stop at the next board
+    pub interrupted: Arc<AtomicBool>,
+}
+
+impl Checkpoint {
+    pub fn new(path: &Path, interval: usize) -> Checkpoint {
+        Checkpoint { path: path.to_path_buf(), interval, interrupted: Arc::new(AtomicBool::new(false)) }
+    }
+
+    fn is_interrupted(self: &Self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Decode(DecodeError),
+    // saved to the checkpoint and stopped
+    Interrupted,
+}
+
+impl std::fmt::Display for CheckpointError {
+    fn fmt(self: &Self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "{}", e),
+            CheckpointError::Json(e) => write!(f, "{}", e),
+            CheckpointError::Decode(e) => write!(f, "{}", e),
+            CheckpointError::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<std::io::Error> for CheckpointError {
+    fn from(e: std::io::Error) -> Self {
+        CheckpointError::Io(e)
+    }
+}
+
+// the checkpoint file is {"nodes": [SavedNode], "expanded": n, "solving": [key] or null},
+// where the nodes are in order of the index with the boards as the keys, the first n nodes are expanded,
+// and "solving" is the decided boards not propagated yet if stopped in the solving
+#[derive(Serialize, Deserialize)]
+struct SavedNode {
+    key: u64,
+    result: String,
+    distance: Option<u32>,
+    next: Vec<SavedEdge>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedEdge {
+    piece: usize,
+    jumps: Vec<usize>,
+    board: u64,
+    swapped: bool,
+}
+
+#[derive(Deserialize)]
+struct SavedAnalysis {
+    nodes: Vec<SavedNode>,
+    expanded: usize,
+    #[serde(default)]
+    solving: Option<Vec<u64>>,
+}
+
+// written node by node, not to copy the whole graph
+struct SavedGraph<'a>(&'a GameGraph);
+
+struct SavingAnalysis<'a>(&'a GameGraph, Option<Vec<u64>>);
+
+impl<'a> Serialize for SavedGraph<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error> where
+        S: Serializer {
+        let graph = self.0;
+        let mut state = serializer.serialize_seq(Some(graph.len()))?;
+        for (index, node) in graph.get_nodes().iter().enumerate() {
+            state.serialize_element(&SavedNode {
+                key: node.key.0,
+                result: node.game_result.get_string().to_string(),
+                distance: node.distance,
+                next: graph.get_edges(index as NodeIndex).iter()
+                    .map(|e| SavedEdge {
+                        piece: usize::from(e.piece_index),
+                        jumps: e.get_jumped_pieces(),
+                        board: graph.get_node(e.next_board).key.0,
+                        swapped: e.colour_swapped,
+                    })
+                    .collect(),
+            })?;
+        }
+        state.end()
+    }
+}
+
+impl<'a> Serialize for SavingAnalysis<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error> where
+        S: Serializer {
+        let mut state = serializer.serialize_struct("SavedAnalysis", 3)?;
+        state.serialize_field("nodes", &SavedGraph(self.0))?;
+        state.serialize_field("expanded", &self.0.get_n_expanded())?;
+        state.serialize_field("solving", &self.1)?;
+        state.end()
+    }
 }
 
 impl Serialize for Code {
@@ -47,330 +251,963 @@ impl Serialize for Code {
     }
 }
 
+/// A move which is the best in a board.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BestMove {
+    pub piece_index: usize,
+    pub next_board: Code,
+    pub game_result: GameResult,
+    // plies to the end of the game from the board before the move
+    pub distance: Option<u32>,
+}
+
+// the decided boards to propagate by `get_propagation_key` of the distance, each in order of the decision
+type DecidedBoards = BTreeMap<(bool, Option<u32>), VecDeque<NodeIndex>>;
+
+// nodes: (code, result), links: (from code, to code, piece index, jumped piece indices)
+// The boards are the stored ones, which may have the colours swapped from the actual next board.
+type NodesAndLinks = (Vec<(String, String)>, Vec<(String, String, usize, Vec<usize>)>);
+
+// results stored out of the analyzer by the rank of the board, e.g. in a loaded `Tablebase`
+pub(crate) trait RankedResults {
+    // unknown if not stored
+    fn get_by_rank(&self, rank: u64) -> (GameResult, Option<u32>);
+
+    // ranks which may be stored, decided or not
+    fn get_stored_ranks(&self) -> Vec<u64>;
+}
+
+/// Retrograde analysis of the boards reachable from the first boards, kept in a `GameGraph`.
+/// The boards are identified by the keys of `Board::to_key`, and the codes are made only for the queries and the exports.
 pub struct Analyzer<B: Board> {
-    map: RefCell<HashMap<Code, AnalysisTreeNode>>,
-    checked_set: RefCell<HashSet<Code>>,
+    // the boards not expanded yet by the search are the last nodes, kept to resume it
+    graph: RefCell<GameGraph>,
+    // storage of the results instead of the graph after each analysis, if any
+    dense_table: RefCell<Option<DenseTable<B>>>,
+    // results of the past analysis read only when asked, e.g. by `from_tablebase`
+    loaded: Option<Box<dyn RankedResults>>,
+    // any analyzed board, to rebuild the boards of the dense table with the same rules
+    sample_board: RefCell<Option<B>>,
+    // the decided boards not propagated yet by the interrupted solving, in order of the distance
+    boards_to_propagate: RefCell<Option<VecDeque<Key>>>,
     _marker: PhantomData<fn() -> B>,
 }
 
+impl<B> Default for Analyzer<B> where B: Board {
+    fn default() -> Self {
+        Analyzer::new()
+    }
+}
+
 impl<B> Analyzer<B> where B: Board {
     pub fn new() -> Analyzer<B> {
         Analyzer {
-            map: RefCell::new(HashMap::new()),
-            checked_set: RefCell::new(HashSet::new()),
+            graph: RefCell::new(GameGraph::new()),
+            dense_table: RefCell::new(None),
+            loaded: None,
+            sample_board: RefCell::new(None),
+            boards_to_propagate: RefCell::new(None),
             _marker: PhantomData,
         }
     }
 
-    pub fn analyze(self: &Self, board: &B) -> GameResult {
-        println!("Start searching leaves.");
+    /// The analyzer which keeps only the results in a `DenseTable` after each analysis, and drops the graph.
+    /// The next boards are generated again for the queries, and nothing is left to emit.
+    /// An error if the table of all legal boards doesn't fit in the memory.
+    pub fn with_dense_table() -> Result<Analyzer<B>, DenseTableError> {
+        Ok(Analyzer {
+            dense_table: RefCell::new(Some(DenseTable::new()?)),
+            ..Analyzer::new()
+        })
+    }
+
+    // the analyzer of the graph analyzed elsewhere, e.g. by `ParallelAnalyzer`
+    pub(crate) fn from_graph(graph: GameGraph, sample_board: Option<B>) -> Analyzer<B> {
+        Analyzer {
+            graph: RefCell::new(graph),
+            sample_board: RefCell::new(sample_board),
+            ..Analyzer::new()
+        }
+    }
+
+    /// Restores the analyzer from `emit_map_as_json`, to query or to continue a past analysis.
+    /// The moves of each board are checked against the current rules of the board.
+    pub fn from_json(json: &str) -> Result<Analyzer<B>, LoadError> {
+        let nodes: HashMap<String, JsonNode> = serde_json::from_str(json).map_err(LoadError::Json)?;
+        let analyzer = Analyzer::new();
+        let mut graph = GameGraph::new();
 
-        let first_board_code = board.encode();
+        for (code, node) in nodes.iter() {
+            let inconsistent = |s: &str| LoadError::Inconsistent(code.clone(), s.to_string());
+            let board = B::decode(&Code(code.clone())).map_err(LoadError::Decode)?;
+            let game_result = GameResult::from_string(&node.result).ok_or_else(|| inconsistent("result"))?;
 
-        self.search(board);
+            if board.get_result() != GameResult::Unknown && board.get_result() != game_result {
+                return Err(inconsistent("result"));
+            }
 
-        println!("Finish searching leaves.");
+            let mut expected_edges: Vec<_> = board.legal_moves().iter()
+                .map(|m| {
+                    let (next_board, colour_swapped) = Self::canonicalize(&board.apply_move(m).unwrap()); // legal move
+                    (m.piece_index, m.jumps.iter().map(|j| j.piece_index).collect(), next_board.encode().0, colour_swapped)
+                })
+                .collect();
+            let mut edges: Vec<_> = node.next.iter().map(|e| (e.piece, e.jumps.clone(), e.board.clone(), e.swapped)).collect();
+            expected_edges.sort();
+            edges.sort();
+            if edges != expected_edges {
+                return Err(inconsistent("moves"));
+            }
+            if let Some(e) = node.next.iter().find(|e| !nodes.contains_key(&e.board)) {
+                return Err(LoadError::Inconsistent(e.board.clone(), "existence".to_string()));
+            }
 
-        println!("Start solving.");
+            if !graph.add_node(board.to_key(), game_result, node.distance).1 {
+                return Err(inconsistent("code"));
+            }
+            analyzer.sample_board.replace(Some(board));
+        }
 
-        let result = self.solve(&first_board_code);
+        // the same order as the nodes
+        for node in nodes.values() {
+            let edges: Vec<_> = node.next.iter()
+                .map(|e| {
+                    let next_board = graph.get_index(&Self::get_key(&Code(e.board.clone())).unwrap()).unwrap(); // same as the legal move
+                    Edge::with_jumps(e.piece, &e.jumps, next_board, e.swapped)
+                })
+                .collect();
+            graph.push_edges(edges);
+        }
+        analyzer.graph.replace(graph);
 
-        println!("Finish solving.");
+        Ok(analyzer)
+    }
 
-        return result;
+    pub fn analyze(self: &Self, board: &B) -> GameResult {
+        return self.analyze_or_interrupt(board, None).unwrap(); // never fails without the checkpoint
     }
 
-    fn search(self: &Self, current_board: &B) {
-        let code = current_board.encode();
-        let next_boards = Rc::new(RefCell::new(Vec::new()));
+    /// Analyzes as `analyze`, saving the progress to the checkpoint every interval and when interrupted.
+    /// The checkpoint file is removed when finished. The dense table of the previous analyses is not saved.
+    pub fn analyze_with_checkpoint(self: &Self, board: &B, checkpoint: &Checkpoint) -> Result<GameResult, CheckpointError> {
+        let result = self.analyze_or_interrupt(board, Some(checkpoint))?;
+
+        match std::fs::remove_file(&checkpoint.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(CheckpointError::Io(e)),
+            _ => {}
+        }
 
-        self.map.borrow_mut().insert(code.clone(), AnalysisTreeNode { game_result: GameResult::Unknown, next_boards: next_boards.clone() });
+        return Ok(result);
+    }
 
-        for i in 0..B::get_board_size() {
-            let child_code = self._search(current_board, i);
-            if let Some(child_code) = child_code {
-                next_boards.borrow_mut().push(child_code);
+    /// Restores the analyzer stopped at the checkpoint, to continue by `analyze_with_checkpoint`.
+    /// The boards are rebuilt with the rules of the given board.
+    pub fn resume(path: &Path, board: &B) -> Result<Analyzer<B>, CheckpointError> {
+        let file = BufReader::new(File::open(path)?);
+        let saved: SavedAnalysis = serde_json::from_reader(file).map_err(CheckpointError::Json)?;
+        let mut graph = GameGraph::new();
+
+        for (index, node) in saved.nodes.iter().enumerate() {
+            let game_result = GameResult::from_string(&node.result)
+                .ok_or(CheckpointError::Decode(DecodeError::Key(node.key)))?;
+            // the boards not expanded yet are rebuilt by the search
+            if index >= saved.expanded {
+                board.rebuild_from_key(&Key(node.key)).map_err(CheckpointError::Decode)?;
+            }
+            if !graph.add_node(Key(node.key), game_result, node.distance).1 {
+                return Err(CheckpointError::Decode(DecodeError::Key(node.key)));
             }
         }
-    }
 
-    fn _search(self: &Self, current_board: &B, piece_index: usize) -> Option<Code> {
-        let next = current_board.move_at(piece_index);
+        for node in saved.nodes.iter().take(saved.expanded) {
+            if !node.next.iter().all(|e| Edge::<NodeIndex>::can_pack(e.piece, &e.jumps)) {
+                return Err(CheckpointError::Decode(DecodeError::Key(node.key)));
+            }
+            let edges = node.next.iter()
+                .map(|e| graph.get_index(&Key(e.board))
+                    .map(|next_board| Edge::with_jumps(e.piece, &e.jumps, next_board, e.swapped))
+                    .ok_or(CheckpointError::Decode(DecodeError::Key(e.board))))
+                .collect::<Result<Vec<_>, _>>()?;
+            graph.push_edges(edges);
+        }
 
-        if let Some(board) = next {
-            let code = board.encode();
-            let result = board.get_result();
-            {
-                let mut map = self.map.borrow_mut();
+        let analyzer = Analyzer::from_graph(graph, Some(board.clone()));
+        analyzer.boards_to_propagate.replace(saved.solving.map(|keys| keys.into_iter().map(Key).collect()));
 
-                if map.contains_key(&code) {
-                    return Option::Some(code);
-                }
+        Ok(analyzer)
+    }
 
+    fn analyze_or_interrupt(self: &Self, board: &B, checkpoint: Option<&Checkpoint>) -> Result<GameResult, CheckpointError> {
+        // the progress is on stderr, not to mix with the output of e.g. the engine protocol
+        eprintln!("Start searching leaves.");
 
-                let next_boards = Rc::new(RefCell::new(Vec::new()));
+        let first_board_key = board.to_key();
 
-                map.insert(board.encode(), AnalysisTreeNode { game_result: result, next_boards: next_boards.clone() });
-            }
+        self.search(board, checkpoint)?;
 
-            if result != GameResult::Unknown {
-                // leaf
-                return Option::Some(code);
-            }
+        eprintln!("Finish searching leaves.");
 
-            let mut codes = Vec::new();
+        eprintln!("Start solving.");
 
-            for i in 0..B::get_board_size() {
-                let child_code = self._search(&board, i);
-                if let Some(child_code) = child_code {
-                    codes.push(child_code);
-                }
-            }
+        let result = self.solve(&first_board_key, checkpoint)?;
 
-            let mut map = self.map.borrow_mut();
-            let tree_node = map.get_mut(&code).unwrap(); // pick already inserted value
-            tree_node.next_boards.borrow_mut().extend_from_slice(&codes);
+        eprintln!("Finish solving.");
 
-            return Option::Some(code);
-        } else {
-            return Option::None;
-        };
+        return Ok(result);
     }
 
-    fn solve(self: &Self, board_code: &Code) -> GameResult {
-        let mut next_boards;
-        // check if already memoized in map
-        {
-            let mut map = self.map.borrow_mut();
-            let tree_node = map.get_mut(board_code);
+    // written to a temporary file first, not to break the last checkpoint when stopped while saving
+    fn save(self: &Self, checkpoint: &Checkpoint, solving: Option<&DecidedBoards>) -> Result<(), CheckpointError> {
+        let mut temporary_path = checkpoint.path.clone().into_os_string();
+        temporary_path.push(".tmp");
 
-            if let Some(tree_node) = tree_node {
-                if tree_node.game_result != GameResult::Unknown {
-                    return tree_node.game_result;
-                }
-                // game_result is Unknown
+        let graph = self.graph.borrow();
+        let solving = solving.map(|boards| boards.values().flatten().map(|i| graph.get_node(*i).key.0).collect());
+        let mut file = BufWriter::new(File::create(&temporary_path)?);
+        serde_json::to_writer(&mut file, &SavingAnalysis(&graph, solving)).map_err(CheckpointError::Json)?;
+        file.flush()?;
+        drop(file);
 
-                if self.checked_set.borrow().contains(board_code) {
-                    // cyclic part
-                    let result = GameResult::Undeterminable;
-                    tree_node.game_result = result;
-                    return result;
-                }
+        std::fs::rename(&temporary_path, &checkpoint.path)?;
 
-                next_boards = { tree_node.next_boards.borrow().clone() };
-            } else {
-                return GameResult::Unknown;
-            }
+        Ok(())
+    }
+
+    // saves every interval of boards, and stops if interrupted
+    fn save_if_needed(self: &Self, checkpoint: Option<&Checkpoint>, n_boards: usize, solving: Option<&DecidedBoards>) -> Result<(), CheckpointError> {
+        let checkpoint = match checkpoint {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(())
         };
 
-        {
-            self.checked_set.borrow_mut().insert(board_code.clone());
+        if checkpoint.is_interrupted() {
+            self.save(checkpoint, solving)?;
+            return Err(CheckpointError::Interrupted);
         }
+        // never for the interval 0
+        if n_boards.is_multiple_of(checkpoint.interval) {
+            self.save(checkpoint, solving)?;
+        }
+
+        Ok(())
+    }
 
-        // calc
-        let results: Vec<_> = next_boards.iter().map(|b| self.solve(b)).collect(); // because side effect function
-        let current_turn = board_code.get_turn::<B>();
+    // keys in the graph are made by `Board::to_key`
+    fn get_code(key: &Key) -> Code {
+        B::from_key(key).unwrap().encode()
+    }
 
-        let (win_turn, win_opposite) = if current_turn == Turn::Red { (GameResult::RedWins, GameResult::YellowWins) } else { (GameResult::YellowWins, GameResult::RedWins) };
-        let result =
-            if results.iter().any(|r| *r == win_turn) {
-                win_turn
-            } else if results.iter().any(|r| *r == GameResult::Undeterminable) {
-                GameResult::Undeterminable
-            } else if results.iter().all(|r| *r == win_opposite) {
-                win_opposite
-            } else {
-                GameResult::Invalid
-            };
+    /// Any board which has been analyzed, e.g. to know the rules.
+    pub fn get_sample_board(self: &Self) -> Option<B> {
+        self.sample_board.borrow().clone()
+    }
 
-        // memoization
-        self.map.borrow_mut().get_mut(board_code).unwrap().game_result = result; // pick already inserted value
+    /// The boards and the moves analyzed so far, e.g. to export them. Empty with the dense table after the analysis.
+    pub fn get_graph(self: &Self) -> Ref<'_, GameGraph> {
+        self.graph.borrow()
+    }
 
-        return result;
+    // None if the code is not of the board
+    fn get_key(code: &Code) -> Option<Key> {
+        B::decode(code).ok().map(|b| b.to_key())
     }
 
-    pub fn emit_map_as_json(self: &Self) -> serde_json::Result<String> {
-        serde_json::to_string(&self.map)
+    // None if the board is not legal, e.g. the first board of an analysis has pieces on the same square
+    fn get_rank(key: &Key) -> Option<u64> {
+        B::from_key(key).ok()?.to_rank()
     }
 
-    pub fn emit_nodes_and_links(self: &Self) -> (Vec<(String, String)>, Vec<(String, String)>) {
-        let mut nodes = Vec::new();
-        let mut links = Vec::new();
+    // the board to store instead of the given one, and whether the colours are swapped for it
+    // Only one of the colour-swapped pair is stored, whose key is the smaller.
+    pub(crate) fn canonicalize(board: &B) -> (B, bool) {
+        match board.get_colour_swapped() {
+            Some(swapped) if swapped.to_key() < board.to_key() => (swapped, true),
+            _ => (board.clone(), false)
+        }
+    }
 
-        for (k, v) in self.map.borrow().iter() {
-            nodes.push((k.0.clone(), v.game_result.get_string().to_string()));
-            for n in v.next_boards.borrow().iter() {
-                links.push((k.0.clone(), n.0.clone()));
-            }
+    fn get_colour_swapped_key(self: &Self, board_key: &Key) -> Option<Key> {
+        let board = self.sample_board.borrow().as_ref()?.rebuild_from_key(board_key).ok()?;
+        board.get_colour_swapped().map(|b| b.to_key())
+    }
+
+    // the finished boards are decided at the distance 0
+    fn get_initial_distance(game_result: GameResult) -> Option<u32> {
+        match game_result {
+            GameResult::RedWins | GameResult::YellowWins => Some(0),
+            _ => None
         }
+    }
 
-        return (nodes, links);
+    // the boards decided in the dense table or the loaded results by the previous analyses are not expanded again
+    fn add_node(self: &Self, graph: &mut GameGraph, board: &B) -> NodeIndex {
+        let key = board.to_key();
+        if let Some(index) = graph.get_index(&key) {
+            return index;
+        }
+
+        let (game_result, distance) = match self.get_by_rank(|| board.to_rank()) {
+            Some((game_result, distance)) if game_result != GameResult::Unknown => (game_result, distance),
+            _ => (board.get_result(), Self::get_initial_distance(board.get_result()))
+        };
+
+        return graph.add_node(key, game_result, distance).0;
     }
-}
 
-#[cfg(test)]
-mod tests {
-    mod solve {
-        use super::super::{Analyzer, AnalysisTreeNode};
-        use crate::game::commons::{Code, GameResult, Turn, Board};
-        use std::cell::RefCell;
-        use std::rc::Rc;
+    // breadth first in order of the index, so the boards not expanded yet are the last nodes of the graph
+    // The boards left by the interrupted search are expanded too.
+    fn search(self: &Self, first_board: &B, checkpoint: Option<&Checkpoint>) -> Result<(), CheckpointError> {
+        self.sample_board.replace(Some(first_board.clone()));
+
+        let (first_board, _) = Self::canonicalize(first_board);
+        let mut graph = self.graph.borrow_mut();
+        if graph.get_index(&first_board.to_key()).is_none() {
+            self.add_node(&mut graph, &first_board);
+            // the new boards are not in the interrupted solving, so it starts over from all decided boards
+            self.boards_to_propagate.replace(None);
+        }
 
-        #[derive(Debug, Copy, Clone)]
-        struct TestBoard {}
+        let mut n_expanded = 0;
 
-        impl Board for TestBoard {
-            fn get_board_size() -> usize {
-                unimplemented!()
+        while graph.get_n_expanded() < graph.len() {
+            let node = *graph.get_node(graph.get_n_expanded() as NodeIndex);
+            // the decided boards have no edges
+            if node.game_result != GameResult::Unknown {
+                graph.push_edges(Vec::new());
+                continue;
             }
 
-            fn move_at(&self, piece_index: usize) -> Option<Self> {
-                unimplemented!()
-            }
+            let board = first_board.rebuild_from_key(&node.key).map_err(CheckpointError::Decode)?;
+            let mut edges = Vec::new();
 
-            fn encode(&self) -> Code {
-                unimplemented!()
-            }
+            for the_move in board.legal_moves() {
+                let (next_board, colour_swapped) = Self::canonicalize(&board.apply_move(&the_move).unwrap()); // legal move
+                let next_index = self.add_node(&mut graph, &next_board);
 
-            fn get_turn_from_code(code: &Code) -> Turn {
-                if let Some(t) = code.0.chars().nth(0) {
-                    match t {
-                        'R' => Turn::Red,
-                        'Y' => Turn::Yellow,
-                        _ => panic!("invalid")
-                    }
-                } else {
-                    panic!("invalid length");
-                }
+                edges.push(Edge::new(&the_move, next_index, colour_swapped));
             }
 
-            fn get_result(&self) -> GameResult {
-                unimplemented!()
-            }
+            graph.push_edges(edges);
 
-            fn draw_ascii_art(&self) -> String {
-                unimplemented!()
+            n_expanded += 1;
+            if checkpoint.is_some() {
+                drop(graph);
+                self.save_if_needed(checkpoint, n_expanded, None)?;
+                graph = self.graph.borrow_mut();
             }
         }
 
-        macro_rules! generate_analyzer_with_game_network_map {
-            ($({$code:expr => $win:ident $(, [$($next_code:expr),+])?}),* $(,)?) => {
-                {
-                    let analyzer = Analyzer::<TestBoard>::new();
-                    {
-                        use crate::game::commons::GameResult::*;
-                        let mut map = analyzer.map.borrow_mut();
-                        $(
-                            map.insert(Code($code.to_string()), AnalysisTreeNode {
-                                game_result: $win,
-                                next_boards: Rc::new(RefCell::new(vec![
-                                    $($(Code($next_code.to_string())),*)?
-                                ])),
-                            });
-                        )*
-                    }
-                    analyzer
-                }
-            };
-        }
+        Ok(())
+    }
 
-        #[test]
-        fn only_1_move() {
-            let analyzer = generate_analyzer_with_game_network_map!(
-                { "R:1" => RedWins },
-            );
+    fn solve(self: &Self, board_key: &Key, checkpoint: Option<&Checkpoint>) -> Result<GameResult, CheckpointError> {
+        self.solve_retrograde(checkpoint)?;
 
-            let result = analyzer.solve(&Code("R:1".to_string()));
+        let result = self.get_solved(board_key).map_or(GameResult::Unknown, |(result, _)| result);
 
-            assert_eq!(result, GameResult::RedWins)
+        if let Some(dense_table) = self.dense_table.borrow_mut().as_mut() {
+            let graph = std::mem::take(&mut *self.graph.borrow_mut());
+            // the boards not legal are dropped
+            for node in graph.get_nodes() {
+                if let Some(rank) = Self::get_rank(&node.key) {
+                    dense_table.set(rank, node.game_result, node.distance);
+                }
+            }
         }
 
-        #[test]
-        fn choose_3_moves_some_can_win() {
-            let analyzer = generate_analyzer_with_game_network_map!(
-                { "R:i" => Unknown, ["Y:f1", "Y:f2", "Y:f3"] },
-                { "Y:f1" => RedWins },
-                { "Y:f2" => YellowWins },
-                { "Y:f3" => Undeterminable },
-            );
-
-            let result = analyzer.solve(&Code("R:i".to_string()));
+        return Ok(result);
+    }
 
-            assert_eq!(result, GameResult::RedWins)
+    // result and distance of the board if analyzed, even if stored with the colours swapped
+    pub(crate) fn get_solved(self: &Self, board_key: &Key) -> Option<(GameResult, Option<u32>)> {
+        if let Some(solved) = self.get_stored(board_key) {
+            return Some(solved);
         }
 
-        #[test]
-        fn choose_2_moves_anyway_lose() {
-            let analyzer = generate_analyzer_with_game_network_map!(
-                { "R:i" => Unknown, ["Y:f1", "Y:f2"] },
-                { "Y:f1" => YellowWins },
-                { "Y:f2" => YellowWins },
-            );
+        let swapped_key = self.get_colour_swapped_key(board_key)?;
+        self.get_stored(&swapped_key).map(|(result, distance)| (result.get_colour_swapped(), distance))
+    }
 
-            let result = analyzer.solve(&Code("R:i".to_string()));
+    // None if not solved yet
+    fn get_stored(self: &Self, board_key: &Key) -> Option<(GameResult, Option<u32>)> {
+        let graph = self.graph.borrow();
+        let stored = match graph.get_index(board_key) {
+            Some(index) => Some((graph.get_node(index).game_result, graph.get_node(index).distance)),
+            None => self.get_by_rank(|| Self::get_rank(board_key))
+        };
 
-            assert_eq!(result, GameResult::YellowWins)
+        match stored {
+            Some((GameResult::Unknown, _)) | None => None,
+            solved => solved
         }
+    }
 
-        #[test]
-        fn choose_2_moves_some_can_drawn() {
-            let analyzer = generate_analyzer_with_game_network_map!(
-                { "R:i" => Unknown, ["Y:f1", "Y:f2"] },
-                { "Y:f1" => YellowWins },
-                { "Y:f2" => Undeterminable },
-            );
+    // None without the dense table and the loaded results, in which case the rank is not asked
+    fn get_by_rank(self: &Self, get_rank: impl FnOnce() -> Option<u64>) -> Option<(GameResult, Option<u32>)> {
+        if let Some(dense_table) = self.dense_table.borrow().as_ref() {
+            return get_rank().map(|rank| dense_table.get(rank));
+        }
 
-            let result = analyzer.solve(&Code("R:i".to_string()));
+        let loaded = self.loaded.as_ref()?;
+        get_rank().map(|rank| loaded.get_by_rank(rank))
+    }
 
-            assert_eq!(result, GameResult::Undeterminable)
+    // ranks of the boards in the graph, the dense table and the loaded results, decided or not, e.g. to write only them
+    pub(crate) fn get_stored_ranks(self: &Self) -> Vec<u64> {
+        let mut ranks: Vec<u64> = self.graph.borrow().get_nodes().iter().filter_map(|node| Self::get_rank(&node.key)).collect();
+        if let Some(dense_table) = self.dense_table.borrow().as_ref() {
+            ranks.extend(dense_table.get_cells().iter().enumerate().filter(|(_, &cell)| cell != 0).map(|(rank, _)| rank as u64));
+        }
+        if let Some(loaded) = self.loaded.as_ref() {
+            ranks.extend(loaded.get_stored_ranks());
         }
 
-        #[test]
-        fn loop_with_all_undeterminable() {
-            let analyzer = generate_analyzer_with_game_network_map!(
-                { "Y:!" => Unknown, ["R:a"] },
-                { "R:a" => Unknown, ["Y:b"] },
-                { "Y:b" => Unknown, ["R:c"] },
-                { "R:c" => Unknown, ["Y:d"] },
-                { "Y:d" => Unknown, ["R:a"] },
-            );
-
-            let result = analyzer.solve(&Code("Y:!".to_string()));
+        return ranks;
+    }
 
-            assert_eq!(result, GameResult::Undeterminable)
+    // (piece index, next board) of the moves in the board if analyzed
+    fn get_next_boards(self: &Self, board_key: &Key) -> Option<Vec<(usize, Key)>> {
+        {
+            let graph = self.graph.borrow();
+            if let Some(index) = graph.get_index(board_key) {
+                return Some(graph.get_edges(index).iter()
+                    .map(|e| {
+                        let next_board = graph.get_node(e.next_board).key;
+                        (usize::from(e.piece_index), if e.colour_swapped { self.get_colour_swapped_key(&next_board).unwrap() } else { next_board })
+                    })
+                    .collect());
+            }
         }
 
-        #[test]
-        fn loop_with_red_wins() {
-            let analyzer = generate_analyzer_with_game_network_map!(
-                { "Y:!" => Unknown, ["R:a"] },
-                { "R:a" => Unknown, ["Y:b"] },
-                { "Y:b" => Unknown, ["R:c"] },
-                { "R:c" => Unknown, ["Y:d", "Y:d'"] },
-                { "Y:d" => Unknown, ["R:a"] },
-                { "Y:d'" => RedWins },
-            );
+        self.get_solved(board_key)?;
+        let board = self.sample_board.borrow().as_ref()?.rebuild_from_key(board_key).ok()?;
 
-            let result = analyzer.solve(&Code("Y:!".to_string()));
+        Some(board.legal_moves().iter()
+            .map(|m| (m.piece_index, board.apply_move(m).unwrap().to_key())) // legal move of the board
+            .collect())
+    }
 
-            assert_eq!(result, GameResult::RedWins)
+    // Propagates the results from the decided boards (e.g. leaves) to their previous boards until nothing changes.
+    // A board wins if any next board wins, and loses if all next boards lose.
+    // The rest can't be decided by either player, so they're undeterminable (drawn by repetition).
+    // The boards are decided in order of the distance, so the first winning next board is the fastest win
+    // and the last losing next board is the slowest lose.
+    // The interrupted solving is resumed from the decided boards which were not propagated yet.
+    pub(crate) fn solve_retrograde(self: &Self, checkpoint: Option<&Checkpoint>) -> Result<(), CheckpointError> {
+        let mut graph = self.graph.borrow_mut();
+        graph.build_previous_edges();
+
+        // only for the expanded boards not decided yet
+        let mut n_undecided_next_boards: Vec<Option<u32>> = vec![None; graph.len()];
+        let mut decided_boards = DecidedBoards::new();
+
+        let interrupted_boards = self.boards_to_propagate.replace(None);
+        let boards_to_propagate: HashSet<Key> = interrupted_boards.iter().flatten().copied().collect();
+        // decided and propagated before the interruption
+        let is_propagated = |key: &Key, result: GameResult| interrupted_boards.is_some()
+            && (result == GameResult::RedWins || result == GameResult::YellowWins)
+            && !boards_to_propagate.contains(key);
+
+        for index in 0..graph.len() as NodeIndex {
+            let node = graph.get_node(index);
+            match node.game_result {
+                GameResult::RedWins | GameResult::YellowWins if interrupted_boards.is_none() =>
+                    decided_boards.entry(Self::get_propagation_key(node.distance)).or_default().push_back(index),
+                GameResult::Unknown if (index as usize) < graph.get_n_expanded() => {
+                    let n_undecided = graph.get_edges(index).iter()
+                        .map(|e| graph.get_node(e.next_board))
+                        .filter(|next_node| !is_propagated(&next_node.key, next_node.game_result))
+                        .count();
+                    n_undecided_next_boards[index as usize] = Some(n_undecided as u32);
+                }
+                _ => {}
+            }
         }
 
-        #[test]
-        fn loop_with_undeterminable_though_yellow_could_win() {
-            let analyzer = generate_analyzer_with_game_network_map!(
-                { "Y:!" => Unknown, ["R:a"] },
-                { "R:a" => Unknown, ["Y:b"] },
-                { "Y:b" => Unknown, ["R:c"] },
-                { "R:c" => Unknown, ["Y:d", "Y:d'"] },
-                { "Y:d" => Unknown, ["R:a"] },
-                { "Y:d'" => YellowWins },
-            );
+        if let Some(boards) = interrupted_boards {
+            for key in boards {
+                let index = graph.get_index(&key).unwrap(); // saved from the graph
+                decided_boards.entry(Self::get_propagation_key(graph.get_node(index).distance)).or_default().push_back(index);
+            }
+        } else {
+            // no moves means it loses
+            for index in (0..graph.len() as NodeIndex).filter(|i| n_undecided_next_boards[*i as usize] == Some(0)) {
+                let node = graph.get_node_mut(index);
+                node.game_result = Self::get_win_of(node.key.get_turn::<B>().get_opposite());
+                node.distance = Some(0);
+                decided_boards.entry(Self::get_propagation_key(Some(0))).or_default().push_back(index);
+            }
+        }
 
-            let result = analyzer.solve(&Code("Y:!".to_string()));
+        let mut n_propagated = 0;
 
-            assert_eq!(result, GameResult::Undeterminable)
-        }
+        // decided in previous analyses have various distances, so the nearest is taken instead of the first decided
+        while let Some(index) = Self::pop_nearest(&mut decided_boards) {
+            let result = graph.get_node(index).game_result;
+            let distance = graph.get_node(index).distance.map(|d| d + 1);
+
+            for i in 0..graph.get_previous_edges(index).len() {
+                let (previous_index, colour_swapped) = graph.get_previous_edges(index)[i];
+                let result = if colour_swapped { result.get_colour_swapped() } else { result };
+                let previous_node = graph.get_node_mut(previous_index);
+                if previous_node.game_result != GameResult::Unknown {
+                    continue;
+                }
+
+                let previous_turn = previous_node.key.get_turn::<B>();
+                let n_undecided = n_undecided_next_boards[previous_index as usize].as_mut().unwrap(); // expanded as a previous board
+                *n_undecided -= 1;
+
+                if result == Self::get_win_of(previous_turn) {
+                    previous_node.game_result = result;
+                    previous_node.distance = distance;
+                    decided_boards.entry(Self::get_propagation_key(distance)).or_default().push_back(previous_index);
+                } else if *n_undecided == 0 {
+                    previous_node.game_result = Self::get_win_of(previous_turn.get_opposite());
+                    previous_node.distance = distance;
+                    decided_boards.entry(Self::get_propagation_key(distance)).or_default().push_back(previous_index);
+                }
+            }
+
+            n_propagated += 1;
+            if checkpoint.is_some() {
+                drop(graph);
+                self.save_if_needed(checkpoint, n_propagated, Some(&decided_boards))?;
+                graph = self.graph.borrow_mut();
+            }
+        }
+
+        for index in (0..graph.len() as NodeIndex).filter(|i| n_undecided_next_boards[*i as usize].is_some()) {
+            let node = graph.get_node_mut(index);
+            if node.game_result == GameResult::Unknown {
+                node.game_result = GameResult::Undeterminable;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn pop_nearest(decided_boards: &mut DecidedBoards) -> Option<NodeIndex> {
+        let mut nearest = decided_boards.first_entry()?;
+        let index = nearest.get_mut().pop_front();
+        if nearest.get().is_empty() {
+            nearest.remove();
+        }
+
+        return index;
+    }
+
+    // The unknown distance, e.g. too far to store in the dense table, is propagated the last as the farthest, as `get_score`.
+    pub(crate) fn get_propagation_key(distance: Option<u32>) -> (bool, Option<u32>) {
+        (distance.is_none(), distance)
+    }
+
+    pub(crate) fn get_win_of(turn: Turn) -> GameResult {
+        if turn == Turn::Red { GameResult::RedWins } else { GameResult::YellowWins }
+    }
+
+    // the larger is the better for the player in the turn
+    // The unknown distance, e.g. too far to store in the dense table, is the farthest.
+    pub(crate) fn get_score(turn: Turn, result: GameResult, distance: Option<u32>) -> (i8, i64) {
+        let distance = distance.map_or(i64::MAX, i64::from);
+
+        if result == Self::get_win_of(turn) {
+            (1, -distance) // faster
+        } else if result == Self::get_win_of(turn.get_opposite()) {
+            (-1, distance) // slower
+        } else {
+            (0, 0)
+        }
+    }
+
+    /// Result of the board if analyzed.
+    pub fn get_result(self: &Self, board_code: &Code) -> Option<GameResult> {
+        let board_key = Self::get_key(board_code)?;
+        self.get_solved(&board_key).map(|(result, _)| result)
+    }
+
+    pub fn get_distance(self: &Self, board_code: &Code) -> Option<u32> {
+        let board_key = Self::get_key(board_code)?;
+        self.get_solved(&board_key).and_then(|(_, distance)| distance)
+    }
+
+    /// Index of the piece to move in the board. The faster win or the slower lose is chosen among the same results.
+    pub fn best_move(self: &Self, board_code: &Code) -> Option<usize> {
+        self.best_moves(board_code).first().map(|m| m.piece_index)
+    }
+
+    /// All moves which are equally the best in the board, in order of the piece index.
+    /// Empty if the board is not analyzed or the game is over.
+    pub fn best_moves(self: &Self, board_code: &Code) -> Vec<BestMove> {
+        match Self::get_key(board_code) {
+            Some(board_key) => self.best_moves_of_key(&board_key).into_iter()
+                .map(|(piece_index, next_board, game_result, distance)| BestMove {
+                    piece_index,
+                    next_board: Self::get_code(&next_board),
+                    game_result,
+                    distance,
+                })
+                .collect(),
+            None => Vec::new()
+        }
+    }
+
+    // (piece index, next board, result, distance) of the best moves
+    pub(crate) fn best_moves_of_key(self: &Self, board_key: &Key) -> Vec<(usize, Key, GameResult, Option<u32>)> {
+        let turn = board_key.get_turn::<B>();
+        let next_boards = match self.get_next_boards(board_key) {
+            Some(next_boards) => next_boards,
+            None => return Vec::new()
+        };
+
+        let candidates: Vec<_> = next_boards.iter()
+            .filter_map(|&(piece_index, next_board)| self.get_solved(&next_board)
+                .map(|(result, distance)| ((piece_index, next_board, result, distance), Self::get_score(turn, result, distance))))
+            .collect();
+        let best_score = match candidates.iter().map(|(_, score)| *score).max() {
+            Some(score) => score,
+            None => return Vec::new()
+        };
+
+        let mut best_moves: Vec<_> = candidates.iter()
+            .filter(|(_, score)| *score == best_score)
+            .map(|&((piece_index, next_board, result, distance), _)| (piece_index, next_board, result, distance.map(|d| d + 1)))
+            .collect();
+        best_moves.sort_by_key(|m| m.0);
+
+        return best_moves;
+    }
+
+    /// The line of boards from the board when both play the best, including the board itself.
+    /// It stops at the end of the game, or at the first repeated board if undeterminable.
+    pub fn principal_variation(self: &Self, board_code: &Code) -> Vec<Code> {
+        let board_key = match Self::get_key(board_code) {
+            Some(key) if self.get_solved(&key).is_some() => key,
+            _ => return Vec::new()
+        };
+
+        let mut line = vec![board_key];
+        let mut appeared = HashSet::new();
+        appeared.insert(board_key);
+
+        while let Some((_, next_board, _, _)) = self.best_moves_of_key(line.last().unwrap()).into_iter().next() {
+            line.push(next_board);
+            if !appeared.insert(next_board) {
+                break;
+            }
+        }
+
+        return line.iter().map(Self::get_code).collect();
+    }
+
+    /// Empty with the dense table, as well as `emit_nodes_and_links`.
+    pub fn emit_map_as_json(self: &Self) -> serde_json::Result<String> {
+        serde_json::to_string(&CodedGraph::<B>(&self.graph.borrow(), PhantomData))
+    }
+
+    pub fn emit_nodes_and_links(self: &Self) -> NodesAndLinks {
+        let graph = self.graph.borrow();
+        let mut nodes = Vec::new();
+        let mut links = Vec::new();
+
+        for (index, node) in graph.get_nodes().iter().enumerate() {
+            let code = Self::get_code(&node.key);
+            for e in graph.get_edges(index as NodeIndex) {
+                links.push((code.0.clone(), Self::get_code(&graph.get_node(e.next_board).key).0, usize::from(e.piece_index), e.get_jumped_pieces()));
+            }
+            nodes.push((code.0, node.game_result.get_string().to_string()));
+        }
+
+        return (nodes, links);
+    }
+}
+
+impl<const N: usize> Analyzer<BoardN<N>> where RuleSet<N>: Default {
+    /// Restores the analyzer from the tablebase of the current rules, which is probed for each query
+    /// instead of holding the results in the memory, so it works for any size of the tablebase.
+    /// All blocks are read once to check the result and the distance of each finished board and each best move
+    /// against the rules.
+    pub fn from_tablebase(path: &Path) -> Result<Self, LoadError> {
+        let tablebase = Tablebase::<N>::open(path).map_err(LoadError::Tablebase)?;
+        let rules = RuleSet::default();
+        let tablebase_rules = RuleSet { first_move: rules.first_move, ..*tablebase.get_rules() };
+        if tablebase_rules != rules {
+            return Err(LoadError::Tablebase(TablebaseError::Rules));
+        }
+
+        let mut inconsistent_board = None;
+        tablebase.for_each_probe(|rank, probe| {
+            if probe.game_result == GameResult::Unknown || inconsistent_board.is_some() {
+                return;
+            }
+
+            let board = BoardN::from_rank_with_rules(rank, rules).unwrap(); // in the range
+            // a win of an unfinished board is at least one move away, unless too far to store
+            let is_consistent = match (board.get_result(), probe.best_move) {
+                (GameResult::Unknown, Some(piece_index)) => board.get_move(piece_index).is_some() && match probe.game_result {
+                    GameResult::RedWins | GameResult::YellowWins => probe.distance != Some(0),
+                    _ => probe.distance.is_none(),
+                },
+                (GameResult::Unknown, None) => board.legal_moves().is_empty() && probe.distance == Some(0),
+                (result, best_move) => result == probe.game_result && best_move.is_none()
+                    && probe.distance == Self::get_initial_distance(result),
+            };
+
+            if !is_consistent {
+                inconsistent_board = Some(board.encode());
+            }
+        }).map_err(LoadError::Tablebase)?;
+
+        if let Some(code) = inconsistent_board {
+            return Err(LoadError::Inconsistent(code.0, "entry".to_string()));
+        }
+
+        Ok(Analyzer {
+            loaded: Some(Box::new(tablebase)),
+            sample_board: RefCell::new(Some(BoardN::with_rules(rules))),
+            ..Analyzer::new()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::commons::{Code, GameResult, Turn, Board, DecodeError, Move, Key};
+    use std::cell::RefCell;
+
+    thread_local! {
+        // codes of the test boards, whose indices are the keys
+        static CODES: RefCell<Vec<Code>> = const { RefCell::new(Vec::new()) };
+    }
+
+    #[derive(Debug, Clone)]
+    struct TestBoard {
+        code: Code,
+    }
+
+    impl Board for TestBoard {
+        fn get_board_size() -> usize {
+            unimplemented!()
+        }
+
+        fn get_move(&self, _piece_index: usize) -> Option<Move> {
+            unimplemented!()
+        }
+
+        fn apply_move(&self, _the_move: &Move) -> Option<Self> {
+            unimplemented!()
+        }
+
+        fn encode(&self) -> Code {
+            self.code.clone()
+        }
+
+        fn get_turn_from_code(code: &Code) -> Turn {
+            if let Some(t) = code.0.chars().nth(0) {
+                match t {
+                    'R' => Turn::Red,
+                    'Y' => Turn::Yellow,
+                    _ => panic!("invalid")
+                }
+            } else {
+                panic!("invalid length");
+            }
+        }
+
+        fn decode(code: &Code) -> Result<Self, DecodeError> {
+            Ok(TestBoard { code: code.clone() })
+        }
+
+        fn to_key(&self) -> Key {
+            let index = CODES.with(|codes| {
+                let mut codes = codes.borrow_mut();
+                codes.iter().position(|c| *c == self.code).unwrap_or_else(|| {
+                    codes.push(self.code.clone());
+                    codes.len() - 1
+                })
+            });
+            let turn = if self.code.get_turn::<TestBoard>() == Turn::Red { 0 } else { 1 };
+
+            Key((index as u64) << 1 | turn)
+        }
+
+        fn get_turn_from_key(key: &Key) -> Turn {
+            if key.0 & 1 == 0 { Turn::Red } else { Turn::Yellow }
+        }
+
+        fn from_key(key: &Key) -> Result<Self, DecodeError> {
+            CODES.with(|codes| codes.borrow().get((key.0 >> 1) as usize).cloned())
+                .map(|code| TestBoard { code })
+                .ok_or(DecodeError::Key(key.0))
+        }
+
+        fn get_n_ranks() -> u64 {
+            unimplemented!()
+        }
+
+        fn to_rank(&self) -> Option<u64> {
+            unimplemented!()
+        }
+
+        fn get_result(&self) -> GameResult {
+            unimplemented!()
+        }
+
+        fn draw_ascii_art(&self) -> String {
+            unimplemented!()
+        }
+    }
+
+    fn key(code: &str) -> Key {
+        TestBoard { code: Code(code.to_string()) }.to_key()
+    }
+
+    macro_rules! generate_analyzer_with_game_network_map {
+        ($({$code:expr => $win:ident $(, [$($next_code:expr),+])?}),* $(,)?) => {
+            {
+                use crate::game::analysis::Analyzer;
+                let analyzer = Analyzer::<crate::game::analysis::tests::TestBoard>::new();
+                {
+                    use crate::game::commons::GameResult::*;
+                    use crate::game::analysis::tests::key;
+                    use crate::game::analysis::tests::TestBoard;
+                    use crate::game::game_graph::Edge;
+                    let mut graph = analyzer.graph.borrow_mut();
+                    $(
+                        graph.add_node(key($code), $win, Analyzer::<TestBoard>::get_initial_distance($win));
+                    )*
+                    // the next boards not listed are added but not expanded, i.e. never decided
+                    $(
+                        let edges: Vec<_> = vec![
+                            $($(key($next_code)),*)?
+                        ].into_iter().enumerate()
+                            .map(|(i, next_board)| Edge::with_jumps(i, &[], graph.add_node(next_board, Unknown, None).0, false))
+                            .collect();
+                        graph.push_edges(edges);
+                    )*
+                }
+                analyzer
+            }
+        };
+    }
+
+    mod solve {
+        use super::key;
+        use super::super::BestMove;
+        use crate::game::commons::{Code, GameResult};
+
+        #[test]
+        fn only_1_move() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "R:1" => RedWins },
+            );
+
+            let result = analyzer.solve(&key("R:1"), None).unwrap();
+
+            assert_eq!(result, GameResult::RedWins)
+        }
+
+        #[test]
+        fn choose_3_moves_some_can_win() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "R:i" => Unknown, ["Y:f1", "Y:f2", "Y:f3"] },
+                { "Y:f1" => RedWins },
+                { "Y:f2" => YellowWins },
+                { "Y:f3" => Undeterminable },
+            );
+
+            let result = analyzer.solve(&key("R:i"), None).unwrap();
+
+            assert_eq!(result, GameResult::RedWins)
+        }
+
+        #[test]
+        fn choose_2_moves_anyway_lose() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "R:i" => Unknown, ["Y:f1", "Y:f2"] },
+                { "Y:f1" => YellowWins },
+                { "Y:f2" => YellowWins },
+            );
+
+            let result = analyzer.solve(&key("R:i"), None).unwrap();
+
+            assert_eq!(result, GameResult::YellowWins)
+        }
+
+        #[test]
+        fn choose_2_moves_some_can_drawn() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "R:i" => Unknown, ["Y:f1", "Y:f2"] },
+                { "Y:f1" => YellowWins },
+                { "Y:f2" => Undeterminable },
+            );
+
+            let result = analyzer.solve(&key("R:i"), None).unwrap();
+
+            assert_eq!(result, GameResult::Undeterminable)
+        }
+
+        #[test]
+        fn loop_with_all_undeterminable() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "Y:!" => Unknown, ["R:a"] },
+                { "R:a" => Unknown, ["Y:b"] },
+                { "Y:b" => Unknown, ["R:c"] },
+                { "R:c" => Unknown, ["Y:d"] },
+                { "Y:d" => Unknown, ["R:a"] },
+            );
+
+            let result = analyzer.solve(&key("Y:!"), None).unwrap();
+
+            assert_eq!(result, GameResult::Undeterminable)
+        }
+
+        #[test]
+        fn loop_with_red_wins() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "Y:!" => Unknown, ["R:a"] },
+                { "R:a" => Unknown, ["Y:b"] },
+                { "Y:b" => Unknown, ["R:c"] },
+                { "R:c" => Unknown, ["Y:d", "Y:d'"] },
+                { "Y:d" => Unknown, ["R:a"] },
+                { "Y:d'" => RedWins },
+            );
+
+            let result = analyzer.solve(&key("Y:!"), None).unwrap();
+
+            assert_eq!(result, GameResult::RedWins)
+        }
+
+        #[test]
+        fn loop_with_undeterminable_though_yellow_could_win() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "Y:!" => Unknown, ["R:a"] },
+                { "R:a" => Unknown, ["Y:b"] },
+                { "Y:b" => Unknown, ["R:c"] },
+                { "R:c" => Unknown, ["Y:d", "Y:d'"] },
+                { "Y:d" => Unknown, ["R:a"] },
+                { "Y:d'" => YellowWins },
+            );
+
+            let result = analyzer.solve(&key("Y:!"), None).unwrap();
+
+            assert_eq!(result, GameResult::Undeterminable)
+        }
+
+        #[test]
+        fn loop_with_red_wins_though_entered_from_the_loop() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "Y:!" => Unknown, ["R:a"] },
+                { "R:a" => Unknown, ["Y:b", "Y:c"] },
+                { "Y:b" => Unknown, ["R:a"] },
+                { "Y:c" => RedWins },
+            );
+
+            let result = analyzer.solve(&key("Y:!"), None).unwrap();
+
+            assert_eq!(result, GameResult::RedWins);
+            assert_eq!(analyzer.get_solved(&key("Y:b")).unwrap().0, GameResult::RedWins);
+        }
 
         #[test]
         fn loop_with_yellow_wins_because_of_outside_of_the_loop() {
@@ -383,9 +1220,783 @@ mod tests {
                 { "Y:d" => Unknown, ["R:a"] },
             );
 
-            let result = analyzer.solve(&Code("Y:!".to_string()));
+            let result = analyzer.solve(&key("Y:!"), None).unwrap();
 
             assert_eq!(result, GameResult::YellowWins)
         }
+
+        #[test]
+        fn choose_the_fastest_win() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "R:i" => Unknown, ["Y:a", "Y:b"] },
+                { "Y:a" => Unknown, ["R:c"] },
+                { "R:c" => Unknown, ["Y:d"] },
+                { "Y:d" => RedWins },
+                { "Y:b" => RedWins },
+            );
+
+            let result = analyzer.solve(&key("R:i"), None).unwrap();
+
+            assert_eq!(result, GameResult::RedWins);
+            assert_eq!(analyzer.get_distance(&Code("R:i".to_string())), Some(1));
+            assert_eq!(analyzer.get_distance(&Code("Y:a".to_string())), Some(2));
+            assert_eq!(analyzer.best_move(&Code("R:i".to_string())), Some(1));
+        }
+
+        #[test]
+        fn choose_the_slowest_lose() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "R:i" => Unknown, ["Y:a", "Y:b"] },
+                { "Y:a" => YellowWins },
+                { "Y:b" => Unknown, ["R:c"] },
+                { "R:c" => Unknown, ["Y:d"] },
+                { "Y:d" => YellowWins },
+            );
+
+            let result = analyzer.solve(&key("R:i"), None).unwrap();
+
+            assert_eq!(result, GameResult::YellowWins);
+            assert_eq!(analyzer.get_distance(&Code("R:i".to_string())), Some(3));
+            assert_eq!(analyzer.best_move(&Code("R:i".to_string())), Some(1));
+        }
+
+        #[test]
+        fn no_distance_of_undeterminable() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "R:i" => Unknown, ["Y:a", "Y:b"] },
+                { "Y:a" => YellowWins },
+                { "Y:b" => Unknown, ["R:i"] },
+            );
+
+            let result = analyzer.solve(&key("R:i"), None).unwrap();
+
+            assert_eq!(result, GameResult::Undeterminable);
+            assert_eq!(analyzer.get_distance(&Code("R:i".to_string())), None);
+            assert_eq!(analyzer.best_move(&Code("R:i".to_string())), Some(1));
+            assert!(analyzer.emit_map_as_json().unwrap().contains(r#""Y:a":{"result":"yellow","distance":0,"#));
+        }
+
+        #[test]
+        fn best_moves_of_equal_results() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "R:i" => Unknown, ["Y:a", "Y:b", "Y:c"] },
+                { "Y:a" => RedWins },
+                { "Y:b" => YellowWins },
+                { "Y:c" => RedWins },
+            );
+
+            analyzer.solve(&key("R:i"), None).unwrap();
+
+            assert_eq!(analyzer.best_moves(&Code("R:i".to_string())), vec![
+                BestMove { piece_index: 0, next_board: Code("Y:a".to_string()), game_result: GameResult::RedWins, distance: Some(1) },
+                BestMove { piece_index: 2, next_board: Code("Y:c".to_string()), game_result: GameResult::RedWins, distance: Some(1) },
+            ]);
+            assert_eq!(analyzer.best_moves(&Code("Y:a".to_string())), vec![]);
+            assert_eq!(analyzer.best_moves(&Code("R:?".to_string())), vec![]);
+        }
+
+        #[test]
+        fn best_moves_of_capped_distances() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "R:i" => Unknown, ["Y:a", "Y:b"] },
+                { "Y:a" => RedWins },
+                { "Y:b" => RedWins },
+                { "Y:j" => Unknown, ["R:c", "R:d"] },
+                { "R:c" => RedWins },
+                { "R:d" => RedWins },
+            );
+            // too far to store, e.g. in the dense table
+            for (code, distance) in [("Y:a", None), ("Y:b", Some(3)), ("R:c", Some(3)), ("R:d", None)].iter() {
+                let mut graph = analyzer.graph.borrow_mut();
+                let index = graph.get_index(&key(code)).unwrap();
+                graph.get_node_mut(index).distance = *distance;
+            }
+
+            // the unknown distance is the farthest
+            assert_eq!(analyzer.best_move(&Code("R:i".to_string())), Some(1));
+            assert_eq!(analyzer.best_move(&Code("Y:j".to_string())), Some(1));
+        }
+
+        #[test]
+        fn nearer_distance_than_capped() {
+            for resumed in [false, true].iter() {
+                let analyzer = generate_analyzer_with_game_network_map!(
+                    { "R:i" => Unknown, ["Y:a", "Y:b"] },
+                    { "Y:a" => RedWins },
+                    { "Y:b" => RedWins },
+                );
+                // decided in previous analyses, Y:a too far to store
+                for (code, distance) in [("Y:a", None), ("Y:b", Some(0))].iter() {
+                    let mut graph = analyzer.graph.borrow_mut();
+                    let index = graph.get_index(&key(code)).unwrap();
+                    graph.get_node_mut(index).distance = *distance;
+                }
+                if *resumed {
+                    analyzer.boards_to_propagate.replace(Some([key("Y:a"), key("Y:b")].iter().copied().collect()));
+                }
+
+                analyzer.solve(&key("R:i"), None).unwrap();
+
+                assert_eq!(analyzer.get_distance(&Code("R:i".to_string())), Some(1));
+            }
+        }
+
+        #[test]
+        fn principal_variation_to_the_end() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "Y:!" => Unknown, ["R:a", "R:b"] },
+                { "R:a" => Unknown, ["Y:c", "Y:d"] },
+                { "R:b" => Unknown, ["Y:e"] },
+                { "Y:c" => YellowWins },
+                { "Y:d" => Unknown, ["R:b"] },
+                { "Y:e" => RedWins },
+            );
+
+            analyzer.solve(&key("Y:!"), None).unwrap();
+
+            assert_eq!(analyzer.principal_variation(&Code("Y:!".to_string())), vec![
+                Code("Y:!".to_string()),
+                Code("R:a".to_string()),
+                Code("Y:d".to_string()),
+                Code("R:b".to_string()),
+                Code("Y:e".to_string()),
+            ]);
+            assert_eq!(analyzer.principal_variation(&Code("Y:?".to_string())), vec![]);
+        }
+
+        #[test]
+        fn principal_variation_of_loop() {
+            let analyzer = generate_analyzer_with_game_network_map!(
+                { "Y:!" => Unknown, ["R:a"] },
+                { "R:a" => Unknown, ["Y:b", "Y:c"] },
+                { "Y:b" => Unknown, ["R:a"] },
+                { "Y:c" => YellowWins },
+            );
+
+            analyzer.solve(&key("Y:!"), None).unwrap();
+
+            assert_eq!(analyzer.principal_variation(&Code("Y:!".to_string())), vec![
+                Code("Y:!".to_string()),
+                Code("R:a".to_string()),
+                Code("Y:b".to_string()),
+                Code("R:a".to_string()),
+            ]);
+        }
+    }
+
+    mod search {
+        use super::super::Analyzer;
+        use crate::game::game_graph::Edge;
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Code, Board, Turn, Position, GameResult, Move, DecodeError, Key};
+
+        const CHAIN_LENGTH: u32 = 200_000;
+
+        // only one move for each board, and red wins at the end
+        #[derive(Debug, Copy, Clone)]
+        struct ChainBoard {
+            step: u32,
+            turn: Turn,
+        }
+
+        impl Board for ChainBoard {
+            fn get_board_size() -> usize {
+                1
+            }
+
+            fn get_move(&self, piece_index: usize) -> Option<Move> {
+                Some(Move { turn: self.turn, piece_index, from: Position::Outward(0), to: Position::Outward(0), jumps: vec![] })
+            }
+
+            fn apply_move(&self, _the_move: &Move) -> Option<Self> {
+                Some(ChainBoard { step: self.step + 1, turn: self.turn.get_opposite() })
+            }
+
+            fn encode(&self) -> Code {
+                Code(format!("{}{}", self.turn, self.step))
+            }
+
+            fn get_turn_from_code(code: &Code) -> Turn {
+                code.0[..1].parse().unwrap()
+            }
+
+            fn decode(code: &Code) -> Result<Self, DecodeError> {
+                let step = code.0[1..].parse().map_err(|_| DecodeError::Format(code.0.clone()))?;
+                Ok(ChainBoard { step, turn: code.0[..1].parse()? })
+            }
+
+            fn to_key(&self) -> Key {
+                Key(u64::from(self.step) << 1 | if self.turn == Turn::Red { 0 } else { 1 })
+            }
+
+            fn get_turn_from_key(key: &Key) -> Turn {
+                if key.0 & 1 == 0 { Turn::Red } else { Turn::Yellow }
+            }
+
+            fn from_key(key: &Key) -> Result<Self, DecodeError> {
+                Ok(ChainBoard { step: (key.0 >> 1) as u32, turn: Self::get_turn_from_key(key) })
+            }
+
+            fn get_n_ranks() -> u64 {
+                2 * (u64::from(CHAIN_LENGTH) + 1)
+            }
+
+            fn to_rank(&self) -> Option<u64> {
+                Some(self.to_key().0)
+            }
+
+            fn get_result(&self) -> GameResult {
+                if self.step == CHAIN_LENGTH { GameResult::RedWins } else { GameResult::Unknown }
+            }
+
+            fn draw_ascii_art(&self) -> String {
+                unimplemented!()
+            }
+        }
+
+        #[test]
+        fn long_chain() {
+            let board = ChainBoard { step: 0, turn: Turn::Yellow };
+            let analyzer = Analyzer::<ChainBoard>::new();
+
+            let result = analyzer.analyze(&board);
+
+            assert_eq!(result, GameResult::RedWins);
+            assert_eq!(analyzer.get_distance(&board.encode()), Some(CHAIN_LENGTH));
+        }
+
+        #[test]
+        fn edges_with_moves() {
+            let board = Board3::decode(&Code("rf_h3h3yf_h3h3tr".to_string())).unwrap();
+            let analyzer = Analyzer::<Board3>::new();
+
+            analyzer.search(&board, None).unwrap();
+
+            let (nodes, mut links) = analyzer.emit_nodes_and_links();
+            links.sort();
+
+            assert_eq!(nodes.len(), 3);
+            assert_eq!(links, vec![
+                ("rf_h3h3yf_h3h3tr".to_string(), "rf_f_h3yf_h3h3ty".to_string(), 1, vec![]),
+                ("rf_h3h3yf_h3h3tr".to_string(), "rf_h3f_yf_h3h3ty".to_string(), 2, vec![]),
+            ]);
+            assert!(analyzer.emit_map_as_json().unwrap().contains(r#"{"piece":1,"jumps":[],"board":"rf_f_h3yf_h3h3ty"}"#));
+        }
+
+        #[test]
+        fn edge_with_jumps() {
+            let mut board = Board3::new(Turn::Red);
+            board.yellow_pieces[0] = Position::Outward(2);
+            board.yellow_pieces[1] = Position::Outward(2);
+
+            let the_move = board.get_move(1).unwrap();
+            let edge = Edge::new(&the_move, board.apply_move(&the_move).unwrap().to_key(), false);
+
+            assert_eq!(edge.piece_index, 1);
+            assert_eq!(edge.get_jumped_pieces(), vec![0, 1]);
+            assert_eq!(Board3::from_key(&edge.next_board).unwrap().encode(), Code("ro0o3o0yo0o0o0ty".to_string()));
+        }
+
+        // the boards decided in the previous analysis are farther than some boards decided in this analysis
+        #[test]
+        fn reuse_boards_decided_far() {
+            let fresh = Analyzer::<Board3>::new();
+            let reusing = Analyzer::<Board3>::new();
+            let board = Board3::decode(&Code("ro3o2f_yf_o3h0tr".to_string())).unwrap();
+
+            fresh.analyze(&board);
+            reusing.analyze(&Board3::decode(&Code("ro3o1f_yf_o2h0ty".to_string())).unwrap());
+            reusing.analyze(&board);
+
+            let (nodes, _) = fresh.emit_nodes_and_links();
+            for code in nodes.iter().map(|(code, _)| Code(code.clone())) {
+                assert_eq!(reusing.get_distance(&code), fresh.get_distance(&code));
+            }
+        }
+    }
+
+    mod dense_table {
+        use super::super::Analyzer;
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Board, Code, GameResult};
+
+        #[test]
+        fn same_as_map() {
+            let with_map = Analyzer::<Board3>::new();
+            let with_dense_table = Analyzer::<Board3>::with_dense_table().unwrap();
+
+            for code in &["ro3o2f_yf_o3h0tr", "ro3o1f_yf_o2h0ty"] {
+                let board = Board3::decode(&Code(code.to_string())).unwrap();
+                assert_eq!(with_dense_table.analyze(&board), with_map.analyze(&board));
+            }
+
+            assert!(with_dense_table.get_graph().is_empty());
+            let (nodes, _) = with_map.emit_nodes_and_links();
+            for code in nodes.iter().map(|(code, _)| Code(code.clone())) {
+                assert_eq!(with_dense_table.get_distance(&code), with_map.get_distance(&code));
+                assert_eq!(with_dense_table.best_moves(&code), with_map.best_moves(&code));
+                assert_eq!(with_dense_table.principal_variation(&code), with_map.principal_variation(&code));
+            }
+        }
+
+        #[test]
+        fn reuse_decided_boards() {
+            let analyzer = Analyzer::<Board3>::with_dense_table().unwrap();
+            let board = Board3::decode(&Code("ro3o2f_yf_o3h0tr".to_string())).unwrap();
+
+            analyzer.analyze(&board);
+            let result = analyzer.analyze(&board);
+
+            assert_eq!(result, GameResult::YellowWins);
+            assert_eq!(analyzer.get_distance(&board.encode()), Some(8));
+            assert_eq!(analyzer.best_moves(&Code("ro0o0o0yo0o0o0tr".to_string())), vec![]);
+        }
+    }
+
+    mod load {
+        use super::super::{Analyzer, LoadError};
+        use crate::game::board3::Board3;
+        use crate::game::board5::Board5;
+        use crate::game::commons::{Board, Code, GameResult};
+        use crate::game::rule_set::RuleSet;
+        use crate::game::tablebase::{Tablebase, TablebaseError};
+        use std::path::PathBuf;
+
+        const CODE: &str = "ro3o2f_yf_o3h0tr";
+
+        fn analyze(rules: RuleSet<3>) -> Analyzer<Board3> {
+            let analyzer = Analyzer::new();
+            analyzer.analyze(&Board3::decode_with_rules(&Code(CODE.to_string()), rules).unwrap());
+            analyzer
+        }
+
+        fn assert_same_queries(loaded: &Analyzer<Board3>, analyzer: &Analyzer<Board3>) {
+            let (nodes, _) = analyzer.emit_nodes_and_links();
+            for code in nodes.iter().map(|(code, _)| Code(code.clone())) {
+                assert_eq!(loaded.get_distance(&code), analyzer.get_distance(&code));
+                assert_eq!(loaded.best_moves(&code), analyzer.best_moves(&code));
+            }
+        }
+
+        fn get_path(name: &str) -> PathBuf {
+            std::env::temp_dir().join(format!("sqdr-load-{}-{}.tb", name, std::process::id()))
+        }
+
+        #[test]
+        fn from_json() {
+            let analyzer = analyze(RuleSet::default());
+
+            let loaded = Analyzer::<Board3>::from_json(&analyzer.emit_map_as_json().unwrap()).unwrap();
+
+            assert_same_queries(&loaded, &analyzer);
+            let (mut nodes, mut links) = loaded.emit_nodes_and_links();
+            let (mut expected_nodes, mut expected_links) = analyzer.emit_nodes_and_links();
+            nodes.sort();
+            links.sort();
+            expected_nodes.sort();
+            expected_links.sort();
+            assert_eq!((nodes, links), (expected_nodes, expected_links));
+            assert_eq!(loaded.analyze(&Board3::decode(&Code(CODE.to_string())).unwrap()), GameResult::YellowWins);
+        }
+
+        #[test]
+        fn from_inconsistent_json() {
+            let json: serde_json::Value = serde_json::from_str(&analyze(RuleSet::default()).emit_map_as_json().unwrap()).unwrap();
+            let load = |json: &serde_json::Value| Analyzer::<Board3>::from_json(&json.to_string());
+
+            let mut other_jumps = json.clone();
+            other_jumps[CODE]["next"][0]["jumps"] = serde_json::json!([0, 1, 2]);
+            assert!(matches!(load(&other_jumps), Err(LoadError::Inconsistent(code, _)) if code == CODE));
+
+            let mut missing_board = json.clone();
+            let next_code = json[CODE]["next"][0]["board"].as_str().unwrap();
+            missing_board.as_object_mut().unwrap().remove(next_code);
+            assert!(matches!(load(&missing_board), Err(LoadError::Inconsistent(code, _)) if code == next_code));
+
+            let mut other_result = json.clone();
+            other_result["rf_f_o0yf_o3h0ty"] = serde_json::json!({ "result": "yellow", "distance": 0, "next": [] });
+            assert!(matches!(load(&other_result), Err(LoadError::Inconsistent(code, _)) if code == "rf_f_o0yf_o3h0ty"));
+
+            let mut invalid_code = json;
+            invalid_code["ro9"] = serde_json::json!({ "result": "unknown", "distance": null, "next": [] });
+            assert!(matches!(load(&invalid_code), Err(LoadError::Decode(_))));
+
+            assert!(matches!(Analyzer::<Board3>::from_json("[]"), Err(LoadError::Json(_))));
+        }
+
+        #[test]
+        fn from_tablebase() {
+            let path = get_path("same");
+            let analyzer = analyze(RuleSet::default());
+            Tablebase::create(&path, &analyzer).unwrap();
+
+            let loaded = Analyzer::<Board3>::from_tablebase(&path).unwrap();
+
+            assert_same_queries(&loaded, &analyzer);
+            assert_eq!(loaded.analyze(&Board3::decode(&Code(CODE.to_string())).unwrap()), GameResult::YellowWins);
+
+            std::fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn from_tablebase_of_other_rules() {
+            let path = get_path("rules");
+            Tablebase::create(&path, &analyze(RuleSet { pieces_to_win: 3, ..RuleSet::default() })).unwrap();
+
+            assert!(matches!(Analyzer::<Board3>::from_tablebase(&path), Err(LoadError::Tablebase(TablebaseError::Rules))));
+
+            std::fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn from_inconsistent_tablebase() {
+            let path = get_path("inconsistent");
+            let analyzer = analyze(RuleSet::default());
+
+            // red wins instead of yellow at the end, with the checksums of the tablebase as usual
+            let (nodes, _) = analyzer.emit_nodes_and_links();
+            let finished = nodes.iter().map(|(code, _)| Board3::decode(&Code(code.clone())).unwrap())
+                .find(|b| b.get_result() == GameResult::YellowWins)
+                .unwrap();
+            {
+                let mut graph = analyzer.graph.borrow_mut();
+                let index = graph.get_index(&finished.to_key()).unwrap();
+                graph.get_node_mut(index).game_result = GameResult::RedWins;
+            }
+            Tablebase::create(&path, &analyzer).unwrap();
+
+            assert!(matches!(Analyzer::<Board3>::from_tablebase(&path), Err(LoadError::Inconsistent(code, _)) if *code == finished.encode().0));
+
+            std::fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn from_tablebase_of_inconsistent_distance() {
+            let path = get_path("distance");
+            let analyzer = analyze(RuleSet::default());
+
+            // the first board wins at once though it's not finished
+            let board = Board3::decode(&Code(CODE.to_string())).unwrap();
+            {
+                let mut graph = analyzer.graph.borrow_mut();
+                let index = graph.get_index(&board.to_key()).unwrap();
+                graph.get_node_mut(index).distance = Some(0);
+            }
+            Tablebase::create(&path, &analyzer).unwrap();
+
+            assert!(matches!(Analyzer::<Board3>::from_tablebase(&path), Err(LoadError::Inconsistent(code, _)) if code == CODE));
+
+            std::fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn from_tablebase_of_board5() {
+            // too many ranks for the dense table, but the tablebase is only probed
+            let path = get_path("board5");
+            let board = Board5::decode(&Code("rf_f_f_h2o0yf_f_f_h3o0tr".to_string())).unwrap();
+            let analyzer = Analyzer::new();
+            analyzer.analyze(&board);
+            Tablebase::create(&path, &analyzer).unwrap();
+
+            let loaded = Analyzer::<Board5>::from_tablebase(&path).unwrap();
+
+            let (nodes, _) = analyzer.emit_nodes_and_links();
+            for code in nodes.iter().map(|(code, _)| Code(code.clone())) {
+                assert_eq!(loaded.get_distance(&code), analyzer.get_distance(&code));
+                assert_eq!(loaded.best_moves(&code), analyzer.best_moves(&code));
+            }
+            assert_eq!(loaded.get_result(&board.encode()), Some(GameResult::YellowWins));
+
+            std::fs::remove_file(path).unwrap();
+        }
     }
-}
\ No newline at end of file
+
+    mod checkpoint {
+        use super::super::{Analyzer, Checkpoint, CheckpointError};
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Board, Code, GameResult};
+        use std::path::PathBuf;
+        use std::sync::atomic::Ordering;
+
+        const CODE: &str = "ro3o2f_yf_o3h0tr";
+
+        fn get_path(name: &str) -> PathBuf {
+            std::env::temp_dir().join(format!("sqdr-checkpoint-{}-{}.json", name, std::process::id()))
+        }
+
+        fn assert_same_results(resumed: &Analyzer<Board3>, analyzer: &Analyzer<Board3>) {
+            let (nodes, _) = analyzer.emit_nodes_and_links();
+            for code in nodes.iter().map(|(code, _)| Code(code.clone())) {
+                assert_eq!(resumed.get_distance(&code), analyzer.get_distance(&code));
+                assert_eq!(resumed.best_moves(&code), analyzer.best_moves(&code));
+            }
+        }
+
+        #[test]
+        fn resume_after_each_interruption() {
+            let board = Board3::decode(&Code(CODE.to_string())).unwrap();
+            let analyzer = Analyzer::new();
+            analyzer.analyze(&board);
+
+            let checkpoint = Checkpoint::new(&get_path("interrupted"), 0);
+            checkpoint.interrupted.store(true, Ordering::SeqCst);
+
+            // stops after every board, in the search and in the solving
+            let mut resumed = Analyzer::new();
+            let mut n_interruptions = 0;
+            let result = loop {
+                match resumed.analyze_with_checkpoint(&board, &checkpoint) {
+                    Ok(result) => break result,
+                    Err(CheckpointError::Interrupted) => n_interruptions += 1,
+                    Err(e) => panic!("{}", e),
+                }
+                resumed = Analyzer::resume(&checkpoint.path, &board).unwrap();
+            };
+
+            assert_eq!(result, GameResult::YellowWins);
+            assert!(n_interruptions > 520);
+            assert!(!checkpoint.path.exists());
+            assert_same_results(&resumed, &analyzer);
+        }
+
+        #[test]
+        fn periodic_saves() {
+            let board = Board3::decode(&Code(CODE.to_string())).unwrap();
+            let analyzer = Analyzer::new();
+            analyzer.analyze(&board);
+
+            let checkpoint = Checkpoint::new(&get_path("periodic"), 100);
+            let saving = Analyzer::new();
+
+            assert_eq!(saving.analyze_with_checkpoint(&board, &checkpoint).unwrap(), GameResult::YellowWins);
+            assert!(!checkpoint.path.exists());
+            assert_same_results(&saving, &analyzer);
+        }
+
+        #[test]
+        fn broken_jumps() {
+            let board = Board3::decode(&Code(CODE.to_string())).unwrap();
+            let checkpoint = Checkpoint::new(&get_path("jumps"), 10);
+            checkpoint.interrupted.store(true, Ordering::SeqCst);
+            // stops after 1 board expanded
+            assert!(matches!(Analyzer::new().analyze_with_checkpoint(&board, &checkpoint), Err(CheckpointError::Interrupted)));
+
+            let mut json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&checkpoint.path).unwrap()).unwrap();
+            json["nodes"][0]["next"][0]["jumps"] = serde_json::json!(vec![0; 20]);
+            std::fs::write(&checkpoint.path, json.to_string()).unwrap();
+
+            assert!(matches!(Analyzer::resume(&checkpoint.path, &board), Err(CheckpointError::Decode(_))));
+
+            std::fs::remove_file(&checkpoint.path).unwrap();
+        }
+
+        #[test]
+        fn no_checkpoint() {
+            let board = Board3::new(crate::game::commons::Turn::Red);
+
+            match Analyzer::resume(&get_path("none"), &board) {
+                Err(CheckpointError::Io(_)) => {}
+                _ => panic!("resumed without the checkpoint"),
+            }
+        }
+    }
+
+    mod colour_swap {
+        use super::super::Analyzer;
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Board, Code, GameResult, Turn, Move, Key, DecodeError};
+        use crate::game::rule_set::RuleSet;
+
+        fn symmetric_rules() -> RuleSet<3> {
+            RuleSet {
+                yellow_speeds_outward: [2, 1, 2],
+                yellow_speeds_homeward: [1, 2, 1],
+                ..RuleSet::default()
+            }
+        }
+
+        // the same board, but the analyzer doesn't know the symmetry
+        #[derive(Debug, Clone)]
+        struct AsymmetricBoard(Board3);
+
+        impl Board for AsymmetricBoard {
+            fn get_board_size() -> usize {
+                Board3::get_board_size()
+            }
+
+            fn get_move(&self, piece_index: usize) -> Option<Move> {
+                self.0.get_move(piece_index)
+            }
+
+            fn apply_move(&self, the_move: &Move) -> Option<Self> {
+                self.0.apply_move(the_move).map(AsymmetricBoard)
+            }
+
+            fn encode(&self) -> Code {
+                self.0.encode()
+            }
+
+            fn get_turn_from_code(code: &Code) -> Turn {
+                Board3::get_turn_from_code(code)
+            }
+
+            fn decode(code: &Code) -> Result<Self, DecodeError> {
+                Board3::decode_with_rules(code, symmetric_rules()).map(AsymmetricBoard)
+            }
+
+            fn to_key(&self) -> Key {
+                self.0.to_key()
+            }
+
+            fn get_turn_from_key(key: &Key) -> Turn {
+                Board3::get_turn_from_key(key)
+            }
+
+            fn from_key(key: &Key) -> Result<Self, DecodeError> {
+                Board3::from_key_with_rules(key, symmetric_rules()).map(AsymmetricBoard)
+            }
+
+            fn get_n_ranks() -> u64 {
+                Board3::get_n_ranks()
+            }
+
+            fn to_rank(&self) -> Option<u64> {
+                self.0.to_rank()
+            }
+
+            fn get_result(&self) -> GameResult {
+                self.0.get_result()
+            }
+
+            fn draw_ascii_art(&self) -> String {
+                self.0.draw_ascii_art()
+            }
+        }
+
+        #[test]
+        fn same_as_without_symmetry() {
+            let board = Board3::decode_with_rules(&Code("ro3o2f_yf_o3h0tr".to_string()), symmetric_rules()).unwrap();
+            let swapped = board.get_colour_swapped().unwrap();
+            let with_symmetry = Analyzer::<Board3>::new();
+            let without_symmetry = Analyzer::<AsymmetricBoard>::new();
+
+            let result = with_symmetry.analyze(&board);
+            let n_stored = with_symmetry.get_graph().len();
+            assert_eq!(with_symmetry.analyze(&swapped), result.get_colour_swapped());
+            assert_eq!(with_symmetry.get_graph().len(), n_stored);
+
+            assert_eq!(without_symmetry.analyze(&AsymmetricBoard(board)), result);
+            assert_eq!(without_symmetry.analyze(&AsymmetricBoard(swapped)), result.get_colour_swapped());
+            assert!(n_stored < without_symmetry.get_graph().len());
+
+            let (nodes, _) = without_symmetry.emit_nodes_and_links();
+            for code in nodes.iter().map(|(code, _)| Code(code.clone())) {
+                assert_eq!(with_symmetry.get_distance(&code), without_symmetry.get_distance(&code));
+                assert_eq!(with_symmetry.best_moves(&code), without_symmetry.best_moves(&code));
+                assert_eq!(with_symmetry.principal_variation(&code), without_symmetry.principal_variation(&code));
+            }
+        }
+    }
+
+    // cargo test --release bench -- --ignored --nocapture
+    mod bench {
+        use super::super::Analyzer;
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Board, Turn, GameResult};
+        use std::collections::HashMap;
+        use std::hash::Hash;
+        use std::mem::size_of;
+        use std::time::{Duration, Instant};
+
+        type Graph<K> = HashMap<K, Vec<K>>;
+
+        // the graph and its reverse of all reachable boards as the analyzer builds them, identified by `identify`
+        fn build_graph<K, F>(identify: F) -> (Graph<K>, Graph<K>, Duration)
+            where K: Hash + Eq + Clone, F: Fn(&Board3) -> K {
+            let start = Instant::now();
+
+            let mut graph = HashMap::new();
+            let mut stack = vec![Board3::new(Turn::Red), Board3::new(Turn::Yellow)];
+            for board in stack.iter() {
+                graph.insert(identify(board), Vec::new());
+            }
+
+            while let Some(board) = stack.pop() {
+                if board.get_result() != GameResult::Unknown {
+                    continue;
+                }
+
+                let mut next_ids = Vec::new();
+                for next_board in board.legal_moves().iter().map(|m| board.apply_move(m).unwrap()) {
+                    let next_id = identify(&next_board);
+                    if !graph.contains_key(&next_id) {
+                        graph.insert(next_id.clone(), Vec::new());
+                        stack.push(next_board);
+                    }
+                    next_ids.push(next_id);
+                }
+                *graph.get_mut(&identify(&board)).unwrap() = next_ids;
+            }
+
+            let mut previous: HashMap<K, Vec<K>> = HashMap::new();
+            for (id, next_ids) in graph.iter() {
+                for next_id in next_ids.iter() {
+                    previous.entry(next_id.clone()).or_default().push(id.clone());
+                }
+            }
+
+            (graph, previous, start.elapsed())
+        }
+
+        // bytes of the ids held by the graphs, without the overhead of the hash maps
+        fn get_bytes<K>(graphs: &[&Graph<K>], get_heap: impl Fn(&K) -> usize) -> usize {
+            graphs.iter().flat_map(|g| g.iter())
+                .map(|(id, ids)| size_of::<K>() + get_heap(id) + ids.iter().map(|i| size_of::<K>() + get_heap(i)).sum::<usize>())
+                .sum()
+        }
+
+        // peak resident memory of the process so far, only on Linux
+        fn get_peak_memory() -> Option<String> {
+            let status = std::fs::read_to_string("/proc/self/status").ok()?;
+            status.lines().find(|l| l.starts_with("VmHWM:")).map(|l| l["VmHWM:".len()..].trim().to_string())
+        }
+
+        #[test]
+        #[ignore]
+        fn keys_against_codes_on_board3() {
+            // the analyzer first, so that the peak memory is of it alone
+            let start = Instant::now();
+            let analyzer = Analyzer::<Board3>::new();
+            analyzer.analyze(&Board3::new(Turn::Red));
+            analyzer.analyze(&Board3::new(Turn::Yellow));
+            let analyze_time = start.elapsed();
+            let analyze_memory = get_peak_memory();
+            drop(analyzer);
+
+            let start = Instant::now();
+            let analyzer = Analyzer::<Board3>::with_dense_table().unwrap();
+            analyzer.analyze(&Board3::new(Turn::Red));
+            analyzer.analyze(&Board3::new(Turn::Yellow));
+            let dense_analyze_time = start.elapsed();
+            drop(analyzer);
+
+            // the graphs alone, as the analyzer builds them, by the codes against by the keys
+            let (code_graph, code_previous, code_time) = build_graph(|b| b.encode());
+            let code_bytes = get_bytes(&[&code_graph, &code_previous], |c| c.0.capacity());
+            drop((code_graph, code_previous));
+
+            let (key_graph, key_previous, key_time) = build_graph(|b| b.to_key());
+            let key_bytes = get_bytes(&[&key_graph, &key_previous], |_| 0);
+            let n_boards = key_graph.len();
+            drop((key_graph, key_previous));
+
+            println!("{} boards", n_boards);
+            println!("Analyzer with Key: {:?}, peak memory {}", analyze_time, analyze_memory.unwrap_or_else(|| "unknown".to_string()));
+            println!("Analyzer with DenseTable of {} bytes: {:?}", Board3::get_n_ranks(), dense_analyze_time);
+            println!("Graph by Code: {:>12} bytes, {:?}", code_bytes, code_time);
+            println!("Graph by Key:  {:>12} bytes, {:?}", key_bytes, key_time);
+
+            assert!(key_bytes * 2 < code_bytes);
+        }
+    }
+}
@@ -1,235 +1,18 @@
-use crate::game::commons::{Board, Position, Turn, GameResult, Code};
-
-const BOARD_SIZE: usize = 3;
-
-#[derive(Debug, Copy, Clone)]
-pub struct Board3 {
-    red_pieces: [Position; BOARD_SIZE],
-    yellow_pieces: [Position; BOARD_SIZE],
-    turn: Turn,
-}
-
-const RED_SPEEDS_OUTWARD: [u8; BOARD_SIZE] = [2, 1, 2];
-const YELLOW_SPEEDS_OUTWARD: [u8; BOARD_SIZE] = [1, 2, 1];
-const RED_SPEEDS_HOMEWARD: [u8; BOARD_SIZE] = [1, 2, 1];
-const YELLOW_SPEEDS_HOMEWARD: [u8; BOARD_SIZE] = [2, 1, 2];
-
-impl Board for Board3 {
-    fn get_board_size() -> usize {
-        BOARD_SIZE
-    }
-
-    fn move_at(self: &Self, piece_index: usize) -> Option<Self> {
-        let mut cloned_self = self.clone();
-
-        let (
-            pieces_turn,
-            pieces_opposite,
-            speed_outward,
-            speed_homeward,
-            next_turn
-        ) = if self.turn == Turn::Red {
-            (&mut cloned_self.red_pieces, &mut cloned_self.yellow_pieces, &RED_SPEEDS_OUTWARD, &RED_SPEEDS_HOMEWARD, Turn::Yellow)
-        } else {
-            (&mut cloned_self.yellow_pieces, &mut cloned_self.red_pieces, &YELLOW_SPEEDS_OUTWARD, &YELLOW_SPEEDS_HOMEWARD, Turn::Red)
-        };
-
-        pieces_turn[piece_index] = match pieces_turn[piece_index] {
-            Position::Outward(n) => {
-                let base_moves = speed_outward[piece_index];
-                let mut jumped_previously = false;
-
-                let mut n_moves = 0;
-                let mut path = n;
-                while n_moves < base_moves || jumped_previously {
-                    n_moves += 1;
-
-                    if path >= BOARD_SIZE as u8 {
-                        // reaches turning point
-                        break;
-                    }
-
-                    let target_piece_index = path as usize;
-                    let target_position = pieces_opposite[target_piece_index];
-                    match target_position {
-                        Position::Outward(m) if m == piece_index as u8 + 1 => {
-                            pieces_opposite[target_piece_index] = Position::Outward(0);
-                            jumped_previously = true;
-                        }
-                        Position::Homeward(m) if m == (BOARD_SIZE - piece_index) as u8 => {
-                            pieces_opposite[target_piece_index] = Position::Homeward(0);
-                            jumped_previously = true;
-                        }
-                        _ => { if jumped_previously { break; } }
-                    }
-                    path += 1;
-                }
-                if n + n_moves > BOARD_SIZE as u8 { Position::Homeward(0) } else { Position::Outward(n + n_moves) }
-            }
-            Position::Homeward(n) => {
-                let base_moves = speed_homeward[piece_index];
-                let mut jumped_previously = false;
-
-                let mut n_moves = 0;
-                let mut path = n;
-                while n_moves < base_moves || jumped_previously {
-                    n_moves += 1;
-
-                    if path >= BOARD_SIZE as u8 {
-                        // reaches finish point
-                        break;
-                    }
-
-                    let target_piece_index = BOARD_SIZE - path as usize - 1;
-                    let target_position = pieces_opposite[target_piece_index];
-                    match target_position {
-                        Position::Outward(m) if m == piece_index as u8 + 1 => {
-                            pieces_opposite[target_piece_index] = Position::Outward(0);
-                            jumped_previously = true;
-                        }
-                        Position::Homeward(m) if m == (BOARD_SIZE - piece_index) as u8 => {
-                            pieces_opposite[target_piece_index] = Position::Homeward(0);
-                            jumped_previously = true;
-                        }
-                        _ => { if jumped_previously { break; } }
-                    }
-                    path += 1;
-                }
-                if n + base_moves > BOARD_SIZE as u8 { Position::Finished } else { Position::Homeward(n + n_moves) }
-            }
-            Position::Finished => return Option::None
-        };
-
-        cloned_self.turn = next_turn;
-
-        Option::Some(cloned_self)
-    }
-
-    fn encode(self: &Self) -> Code {
-        Code(format!("r{}{}{}y{}{}{}t{}",
-                     self.red_pieces[0],
-                     self.red_pieces[1],
-                     self.red_pieces[2],
-                     self.yellow_pieces[0],
-                     self.yellow_pieces[1],
-                     self.yellow_pieces[2],
-                     if self.turn == Turn::Red { "r" } else { "y" }
-        ).to_string())
-    }
-
-    fn get_turn_from_code(code: &Code) -> Turn {
-        if code.0.ends_with("r") { Turn::Red } else { Turn::Yellow }
-    }
-
-    fn get_result(self: &Self) -> GameResult {
-        let is_red_finished = self.red_pieces.iter().filter(|&p| *p == Position::Finished).count() >= BOARD_SIZE - 1;
-        let is_yellow_finished = self.yellow_pieces.iter().filter(|&p| *p == Position::Finished).count() >= BOARD_SIZE - 1;
-
-        match (is_red_finished, is_yellow_finished) {
-            (false, false) => GameResult::Unknown,
-            (true, false) => GameResult::RedWins,
-            (false, true) => GameResult::YellowWins,
-            (true, true) => GameResult::Invalid
-        }
-    }
-
-    //               .   :   .  next-> Yellow
-    //         +---+---+---+---+---+
-    //         |===| v |   | v |===|
-    //         +---+---+---+---+---+
-    //     . 2 | > |   |   |   |   | :
-    //         +---+---+---+---+---+
-    //     : 1 |   |   |   |   | < | .
-    //         +---+---+---+---+---+
-    //     . 0 | > |   |   |   |   | :
-    //         +---+---+---+---+---+
-    //         |===|   | ^ |   |===|
-    //         +---+---+---+---+---+
-    // Yellow /      0   1   2
-    //       / Red   :   .   :
-    fn draw_ascii_art(self: &Self) -> String {
-        const EMPTY: &str = "   ";
-        const UP: &str = " ^ ";
-        const RIGHT: &str = " > ";
-        const DOWN: &str = " v ";
-        const LEFT: &str = " < ";
-
-        let r0t = if self.red_pieces[0] == Position::Homeward(0) { DOWN } else { EMPTY };
-        let r1t = if self.red_pieces[1] == Position::Homeward(0) { DOWN } else { EMPTY };
-        let r2t = if self.red_pieces[2] == Position::Homeward(0) { DOWN } else { EMPTY };
-
-        let y0s = match self.yellow_pieces[0] {
-            Position::Outward(0) => RIGHT,
-            Position::Finished => LEFT,
-            _ => EMPTY,
-        };
-        let y1s = match self.yellow_pieces[1] {
-            Position::Outward(0) => RIGHT,
-            Position::Finished => LEFT,
-            _ => EMPTY,
-        };
-        let y2s = match self.yellow_pieces[2] {
-            Position::Outward(0) => RIGHT,
-            Position::Finished => LEFT,
-            _ => EMPTY,
-        };
-
-        let r0s = match self.red_pieces[0] {
-            Position::Outward(0) => UP,
-            Position::Finished => DOWN,
-            _ => EMPTY
-        };
-        let r1s = match self.red_pieces[1] {
-            Position::Outward(0) => UP,
-            Position::Finished => DOWN,
-            _ => EMPTY
-        };
-        let r2s = match self.red_pieces[2] {
-            Position::Outward(0) => UP,
-            Position::Finished => DOWN,
-            _ => EMPTY
-        };
-
-        let board = self;
-        let get_square = |yellow_index: usize, red_index: usize| -> &'static str {
-            match (board.red_pieces[red_index], board.yellow_pieces[yellow_index]) {
-                (Position::Outward(n), _) if n == yellow_index as u8 + 1 => UP,
-                (Position::Homeward(n), _) if n == (BOARD_SIZE - yellow_index) as u8 => DOWN,
-                (_, Position::Outward(n)) if n == red_index as u8 + 1 => RIGHT,
-                (_, Position::Homeward(n)) if n == (BOARD_SIZE - red_index) as u8 => LEFT,
-                _ => EMPTY
-            }
-        };
-
-        let y0t = if self.yellow_pieces[0] == Position::Homeward(0) { LEFT } else { EMPTY };
-        let y1t = if self.yellow_pieces[1] == Position::Homeward(0) { LEFT } else { EMPTY };
-        let y2t = if self.yellow_pieces[2] == Position::Homeward(0) { LEFT } else { EMPTY };
-
-        return [
-            format!("              .   :   .  next-> {}", if self.turn == Turn::Red { "Red" } else { "Yellow" }),
-            format!("        +---+---+---+---+---+"),
-            format!("        |===|{0}|{1}|{2}|===|", r0t, r1t, r2t),
-            format!("        +---+---+---+---+---+"),
-            format!("    . 2 |{0}|{1}|{2}|{3}|{4}| :", y2s, get_square(2, 0), get_square(2, 1), get_square(2, 2), y2t),
-            format!("        +---+---+---+---+---+"),
-            format!("    : 1 |{0}|{1}|{2}|{3}|{4}| .", y1s, get_square(1, 0), get_square(1, 1), get_square(1, 2), y1t),
-            format!("        +---+---+---+---+---+"),
-            format!("    . 0 |{0}|{1}|{2}|{3}|{4}| :", y0s, get_square(0, 0), get_square(0, 1), get_square(0, 2), y0t),
-            format!("        +---+---+---+---+---+"),
-            format!("        |===|{0}|{1}|{2}|===|", r0s, r1s, r2s),
-            format!("        +---+---+---+---+---+"),
-            format!("Yellow /      0   1   2"),
-            format!("      / Red   :   .   :"),
-        ].join("\n");
-    }
-}
-
-impl Board3 {
-    pub fn new(the_first_move: Turn) -> Self {
-        Board3 {
-            red_pieces: [Position::Outward(0); BOARD_SIZE],
-            yellow_pieces: [Position::Outward(0); BOARD_SIZE],
-            turn: the_first_move,
+use crate::game::board_n::BoardN;
+use crate::game::commons::Turn;
+use crate::game::rule_set::RuleSet;
+
+pub type Board3 = BoardN<3>;
+
+impl Default for RuleSet<3> {
+    fn default() -> Self {
+        RuleSet {
+            red_speeds_outward: [2, 1, 2],
+            yellow_speeds_outward: [1, 2, 1],
+            red_speeds_homeward: [1, 2, 1],
+            yellow_speeds_homeward: [2, 1, 2],
+            pieces_to_win: 2,
+            first_move: Turn::Red,
         }
     }
 }
@@ -614,4 +397,204 @@ mod tests {
                 "      / Red   :   .   :"].join("\n"));
         }
     }
-}
\ No newline at end of file
+
+    mod decode {
+        use super::super::Board3;
+        use crate::game::commons::{Turn, Board, Position, GameResult, Code, DecodeError, Key};
+        use crate::game::rule_set::RuleSet;
+        use std::collections::HashSet;
+
+        #[test]
+        fn initial_board() {
+            let board = Board3::decode(&Code("ro0o0o0yo0o0o0tr".to_string())).unwrap();
+
+            assert_eq!(board, Board3::new(Turn::Red));
+        }
+
+        #[test]
+        fn round_trip_all_reachable_boards() {
+            let mut checked = HashSet::new();
+            let mut stack = vec![Board3::new(Turn::Red), Board3::new(Turn::Yellow)];
+
+            while let Some(board) = stack.pop() {
+                let code = board.encode();
+                if !checked.insert(code.clone()) {
+                    continue;
+                }
+
+                let decoded = Board3::decode(&code).unwrap();
+                assert_eq!(decoded, board);
+                assert_eq!(decoded.encode(), code);
+                assert_eq!(Board3::from_key(&board.to_key()), Ok(board));
+                assert_eq!(board.to_key().get_turn::<Board3>(), board.turn);
+                assert_eq!(Board3::from_rank_with_rules(board.to_rank().unwrap(), RuleSet::default()), Some(board));
+
+                if board.get_result() == GameResult::Unknown {
+                    stack.extend((0..Board3::get_board_size()).filter_map(|i| board.move_at(i)));
+                }
+            }
+
+            assert!(checked.len() > 1);
+        }
+
+        #[test]
+        fn jumped_at_last_square() {
+            let board = Board3::decode(&Code("ro0o0h4yo0o0o0ty".to_string())).unwrap();
+
+            assert_eq!(board.red_pieces[2], Position::Homeward(4));
+        }
+
+        #[test]
+        fn invalid_format() {
+            for code in &["", "o0o0o0yo0o0o0tr", "ro0o0o0o0o0o0tr", "ro0o0yo0o0o0tr", "ro0o0o0o0yo0o0o0tr", "ro0o0o0yo0o0o0", "r1o0o0yo0o0o0tr"] {
+                assert_eq!(Board3::decode(&Code(code.to_string())), Err(DecodeError::Format(code.to_string())));
+            }
+        }
+
+        #[test]
+        fn invalid_position() {
+            assert_eq!(Board3::decode(&Code("ro0o4o0yo0o0o0tr".to_string())), Err(DecodeError::Position("o4".to_string())));
+            assert_eq!(Board3::decode(&Code("ro0o0o0yo0h5o0tr".to_string())), Err(DecodeError::Position("h5".to_string())));
+            assert_eq!(Board3::decode(&Code("ro0o0o0yo0f1o0tr".to_string())), Err(DecodeError::Position("f1".to_string())));
+        }
+
+        #[test]
+        fn invalid_turn() {
+            assert_eq!(Board3::decode(&Code("ro0o0o0yo0o0o0tx".to_string())), Err(DecodeError::Turn("x".to_string())));
+        }
+
+        #[test]
+        fn keys_of_initial_boards() {
+            assert_eq!(Board3::new(Turn::Red).to_key(), Key(0));
+            assert_eq!(Board3::new(Turn::Yellow).to_key(), Key(1));
+        }
+
+        #[test]
+        fn ranks() {
+            assert_eq!(Board3::get_n_ranks(), 1_336_832);
+            assert_eq!(Board3::new(Turn::Yellow).to_rank(), Some(1));
+            assert_eq!(Board3::from_rank_with_rules(Board3::get_n_ranks() - 1, RuleSet::default()).unwrap().encode(),
+                       Code("rf_f_f_yf_f_f_ty".to_string()));
+            assert_eq!(Board3::from_rank_with_rules(Board3::get_n_ranks(), RuleSet::default()), None);
+        }
+
+        #[test]
+        fn invalid_key() {
+            // index 10 of the first red piece, and a bit over the 6 pieces
+            assert_eq!(Board3::from_key(&Key(10 << 1)), Err(DecodeError::Key(10 << 1)));
+            assert_eq!(Board3::from_key(&Key(1 << 25)), Err(DecodeError::Key(1 << 25)));
+        }
+    }
+
+    mod get_move {
+        use super::super::Board3;
+        use crate::game::commons::{Turn, Board, Position, Move, Jump};
+
+        #[test]
+        fn move_r1o0_then_jump_y0o2_and_y1o2() {
+            let mut board = Board3::new(Turn::Red);
+
+            board.yellow_pieces[0] = Position::Outward(2);
+            board.yellow_pieces[1] = Position::Outward(2);
+
+            let the_move = board.get_move(1).unwrap();
+
+            assert_eq!(the_move, Move {
+                turn: Turn::Red,
+                piece_index: 1,
+                from: Position::Outward(0),
+                to: Position::Outward(3),
+                jumps: vec![
+                    Jump { piece_index: 0, from: Position::Outward(2), to: Position::Outward(0) },
+                    Jump { piece_index: 1, from: Position::Outward(2), to: Position::Outward(0) },
+                ],
+            });
+            assert_eq!(the_move.to_string(), "r1:o0-o3 y0:o2-o0 y1:o2-o0");
+        }
+
+        #[test]
+        fn move_y2h0_then_jump_r2h1() {
+            let mut board = Board3::new(Turn::Yellow);
+
+            board.red_pieces[2] = Position::Homeward(1);
+            board.yellow_pieces[2] = Position::Homeward(0);
+
+            let the_move = board.get_move(2).unwrap();
+
+            assert_eq!(the_move.to, Position::Homeward(2));
+            assert_eq!(the_move.jumps, vec![Jump { piece_index: 2, from: Position::Homeward(1), to: Position::Homeward(0) }]);
+            assert_eq!(board.apply_move(&the_move), board.move_at(2));
+        }
+
+        #[test]
+        fn finished() {
+            let mut board = Board3::new(Turn::Red);
+
+            board.red_pieces[2] = Position::Finished;
+
+            assert_eq!(board.get_move(2), None);
+        }
+    }
+
+    mod legal_moves {
+        use super::super::Board3;
+        use crate::game::commons::{Turn, Board, Position};
+
+        #[test]
+        fn initial_board() {
+            let board = Board3::new(Turn::Yellow);
+
+            let moves = board.legal_moves();
+
+            assert_eq!(moves.iter().map(|m| m.piece_index).collect::<Vec<_>>(), vec![0, 1, 2]);
+            assert!(moves.iter().all(|m| m.turn == Turn::Yellow && m.jumps.is_empty()));
+        }
+
+        #[test]
+        fn without_finished_piece() {
+            let mut board = Board3::new(Turn::Red);
+
+            board.red_pieces[1] = Position::Finished;
+
+            assert_eq!(board.legal_moves().iter().map(|m| m.piece_index).collect::<Vec<_>>(), vec![0, 2]);
+        }
+
+        #[test]
+        fn game_over() {
+            let mut board = Board3::new(Turn::Yellow);
+
+            board.red_pieces[0] = Position::Finished;
+            board.red_pieces[1] = Position::Finished;
+
+            assert!(board.legal_moves().is_empty());
+        }
+    }
+
+    mod apply_move {
+        use super::super::Board3;
+        use crate::game::commons::{Turn, Board, Position};
+
+        #[test]
+        fn move_of_another_board() {
+            let board = Board3::new(Turn::Red);
+            let the_move = board.get_move(0).unwrap();
+
+            let mut moved_board = board;
+            moved_board.red_pieces[0] = Position::Outward(1);
+
+            assert_eq!(moved_board.apply_move(&the_move), None);
+            assert_eq!(Board3::new(Turn::Yellow).apply_move(&the_move), None);
+        }
+
+        #[test]
+        fn jumped_piece_has_gone() {
+            let mut board = Board3::new(Turn::Red);
+            board.yellow_pieces[0] = Position::Outward(1);
+            let the_move = board.get_move(0).unwrap();
+
+            board.yellow_pieces[0] = Position::Outward(2);
+
+            assert_eq!(board.apply_move(&the_move), None);
+        }
+    }
+}
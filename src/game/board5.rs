@@ -0,0 +1,391 @@
+use crate::game::board_n::BoardN;
+use crate::game::commons::Turn;
+use crate::game::rule_set::RuleSet;
+
+pub type Board5 = BoardN<5>;
+
+impl Default for RuleSet<5> {
+    fn default() -> Self {
+        RuleSet {
+            red_speeds_outward: [1, 3, 2, 3, 1],
+            yellow_speeds_outward: [3, 1, 2, 1, 3],
+            red_speeds_homeward: [3, 1, 2, 1, 3],
+            yellow_speeds_homeward: [1, 3, 2, 3, 1],
+            pieces_to_win: 4,
+            first_move: Turn::Red,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //           *   .   :   .   *
+    //     +---+---+---+---+---+---+---+
+    //     |===| h0| h0| h0| h0| h0|===|
+    //     +---+---+---+---+---+---+---+
+    // * 4 |o0f|   |   |   |   |   | h0| .
+    //     +---+---+---+---+---+---+---+
+    // . 3 |o0f|   |   |   |   |   | h0| *
+    //     +---+---+---+---+---+---+---+
+    // : 2 |o0f|   |   |   |   |   | h0| :
+    //     +---+---+---+---+---+---+---+
+    // . 1 |o0f|   |   |   |   |   | h0| *
+    //     +---+---+---+---+---+---+---+
+    // * 0 |o0f|   |   |   |   |   | h0| .
+    //     +---+---+---+---+---+---+---+
+    //     |===|o0f|o0f|o0f|o0f|o0f|===|
+    //     +---+---+---+---+---+---+---+
+    //           0   1   2   3   4
+    //           .   *   :   *   .
+
+    mod move_at {
+        use super::super::Board5;
+        use crate::game::commons::{Turn, Board, Position};
+
+        #[test]
+        fn move_r0o0() {
+            let board = Board5::new(Turn::Red);
+
+            let board = board.move_at(0).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Outward(1), Position::Outward(0), Position::Outward(0), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0); 5]);
+        }
+
+        #[test]
+        fn move_r1o0() {
+            let board = Board5::new(Turn::Red);
+
+            let board = board.move_at(1).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Outward(0), Position::Outward(3), Position::Outward(0), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0); 5]);
+        }
+
+        #[test]
+        fn move_y0o0() {
+            let board = Board5::new(Turn::Yellow);
+
+            let board = board.move_at(0).unwrap();
+
+            assert_eq!(board.turn, Turn::Red);
+            assert_eq!(board.red_pieces, [Position::Outward(0); 5]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(3), Position::Outward(0), Position::Outward(0), Position::Outward(0), Position::Outward(0)]);
+        }
+
+        #[test]
+        fn move_r0o5() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.red_pieces[0] = Position::Outward(5);
+
+            let board = board.move_at(0).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Homeward(0), Position::Outward(0), Position::Outward(0), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0); 5]);
+        }
+
+        #[test]
+        fn move_r1o4() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.red_pieces[1] = Position::Outward(4);
+
+            let board = board.move_at(1).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Outward(0), Position::Homeward(0), Position::Outward(0), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0); 5]);
+        }
+
+        #[test]
+        fn move_r0o0_then_jump_y0o1() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.yellow_pieces[0] = Position::Outward(1);
+
+            let board = board.move_at(0).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Outward(2), Position::Outward(0), Position::Outward(0), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0); 5]);
+        }
+
+        #[test]
+        fn move_r0o0_then_not_jump_y1o1() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.yellow_pieces[1] = Position::Outward(1);
+
+            let board = board.move_at(0).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Outward(1), Position::Outward(0), Position::Outward(0), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0), Position::Outward(1), Position::Outward(0), Position::Outward(0), Position::Outward(0)]);
+        }
+
+        #[test]
+        fn move_r2o0_then_jump_y0o3_and_y1o3() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.yellow_pieces[0] = Position::Outward(3);
+            board.yellow_pieces[1] = Position::Outward(3);
+
+            let board = board.move_at(2).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Outward(0), Position::Outward(0), Position::Outward(3), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0); 5]);
+        }
+
+        #[test]
+        fn move_r2o0_then_jump_y0o3_but_not_y2o3() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.yellow_pieces[0] = Position::Outward(3);
+            board.yellow_pieces[2] = Position::Outward(3);
+
+            let board = board.move_at(2).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Outward(0), Position::Outward(0), Position::Outward(2), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0), Position::Outward(0), Position::Outward(3), Position::Outward(0), Position::Outward(0)]);
+        }
+
+        #[test]
+        fn move_r2o0_then_jump_all_to_turning_point() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.yellow_pieces = [Position::Outward(3); 5];
+
+            let board = board.move_at(2).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Outward(0), Position::Outward(0), Position::Homeward(0), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0); 5]);
+        }
+
+        #[test]
+        fn move_r0o0_then_jump_y0h5() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.yellow_pieces[0] = Position::Homeward(5);
+
+            let board = board.move_at(0).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Outward(2), Position::Outward(0), Position::Outward(0), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Homeward(0), Position::Outward(0), Position::Outward(0), Position::Outward(0), Position::Outward(0)]);
+        }
+
+        #[test]
+        fn move_r0h0() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.red_pieces[0] = Position::Homeward(0);
+
+            let board = board.move_at(0).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Homeward(3), Position::Outward(0), Position::Outward(0), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0); 5]);
+        }
+
+        #[test]
+        fn move_r1h0_then_jump_y4o2() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.red_pieces[1] = Position::Homeward(0);
+            board.yellow_pieces[4] = Position::Outward(2);
+
+            let board = board.move_at(1).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Outward(0), Position::Homeward(2), Position::Outward(0), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0); 5]);
+        }
+
+        #[test]
+        fn move_y4h4() {
+            let mut board = Board5::new(Turn::Yellow);
+
+            board.yellow_pieces[4] = Position::Homeward(4);
+
+            let board = board.move_at(4).unwrap();
+
+            assert_eq!(board.turn, Turn::Red);
+            assert_eq!(board.red_pieces, [Position::Outward(0); 5]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0), Position::Outward(0), Position::Outward(0), Position::Outward(0), Position::Homeward(5)]);
+        }
+
+        #[test]
+        fn move_r0h5() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.red_pieces[0] = Position::Homeward(5);
+
+            let board = board.move_at(0).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Finished, Position::Outward(0), Position::Outward(0), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0); 5]);
+        }
+
+        #[test]
+        fn move_finished() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.red_pieces[3] = Position::Finished;
+
+            assert!(board.move_at(3).is_none());
+        }
+    }
+
+    mod get_result {
+        use super::super::Board5;
+        use crate::game::commons::{Turn, Board, Position, GameResult};
+
+        #[test]
+        fn three_finished_is_unknown() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.red_pieces[0] = Position::Finished;
+            board.red_pieces[1] = Position::Finished;
+            board.red_pieces[2] = Position::Finished;
+
+            assert_eq!(board.get_result(), GameResult::Unknown);
+        }
+
+        #[test]
+        fn four_finished_wins() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.yellow_pieces[0] = Position::Finished;
+            board.yellow_pieces[1] = Position::Finished;
+            board.yellow_pieces[3] = Position::Finished;
+            board.yellow_pieces[4] = Position::Finished;
+
+            assert_eq!(board.get_result(), GameResult::YellowWins);
+        }
+    }
+
+    mod encode {
+        use super::super::Board5;
+        use crate::game::commons::{Turn, Board, Position, Code};
+
+        #[test]
+        fn initial_board() {
+            let board = Board5::new(Turn::Yellow);
+
+            assert_eq!(board.encode(), Code("ro0o0o0o0o0yo0o0o0o0o0ty".to_string()));
+            assert_eq!(Board5::get_turn_from_code(&board.encode()), Turn::Yellow);
+        }
+
+        #[test]
+        fn mixed_board() {
+            let mut board = Board5::new(Turn::Red);
+
+            board.red_pieces[1] = Position::Homeward(2);
+            board.red_pieces[4] = Position::Finished;
+            board.yellow_pieces[2] = Position::Outward(5);
+
+            assert_eq!(board.encode(), Code("ro0h2o0o0f_yo0o0o5o0o0tr".to_string()));
+            assert_eq!(Board5::get_turn_from_code(&board.encode()), Turn::Red);
+        }
+    }
+
+    mod draw_ascii_art {
+        use super::super::Board5;
+        use crate::game::commons::{Turn, Board, Position};
+
+        #[test]
+        fn initial_board() {
+            let board = Board5::new(Turn::Red);
+
+            assert_eq!(board.draw_ascii_art(), [
+                "              *   .   :   .   *  next-> Red",
+                "        +---+---+---+---+---+---+---+",
+                "        |===|   |   |   |   |   |===|",
+                "        +---+---+---+---+---+---+---+",
+                "    * 4 | > |   |   |   |   |   |   | .",
+                "        +---+---+---+---+---+---+---+",
+                "    . 3 | > |   |   |   |   |   |   | *",
+                "        +---+---+---+---+---+---+---+",
+                "    : 2 | > |   |   |   |   |   |   | :",
+                "        +---+---+---+---+---+---+---+",
+                "    . 1 | > |   |   |   |   |   |   | *",
+                "        +---+---+---+---+---+---+---+",
+                "    * 0 | > |   |   |   |   |   |   | .",
+                "        +---+---+---+---+---+---+---+",
+                "        |===| ^ | ^ | ^ | ^ | ^ |===|",
+                "        +---+---+---+---+---+---+---+",
+                "Yellow /      0   1   2   3   4",
+                "      / Red   .   *   :   *   ."].join("\n"));
+        }
+
+        #[test]
+        fn scattered() {
+            let mut board = Board5::new(Turn::Yellow);
+
+            board.red_pieces[0] = Position::Outward(1);
+            board.red_pieces[1] = Position::Homeward(0);
+            board.red_pieces[2] = Position::Homeward(3);
+            board.red_pieces[4] = Position::Finished;
+            board.yellow_pieces[1] = Position::Homeward(0);
+            board.yellow_pieces[3] = Position::Outward(4);
+            board.yellow_pieces[4] = Position::Finished;
+
+            assert_eq!(board.draw_ascii_art(), [
+                "              *   .   :   .   *  next-> Yellow",
+                "        +---+---+---+---+---+---+---+",
+                "        |===|   | v |   |   |   |===|",
+                "        +---+---+---+---+---+---+---+",
+                "    * 4 | < |   |   |   |   |   |   | .",
+                "        +---+---+---+---+---+---+---+",
+                "    . 3 |   |   |   |   | > |   |   | *",
+                "        +---+---+---+---+---+---+---+",
+                "    : 2 | > |   |   | v |   |   |   | :",
+                "        +---+---+---+---+---+---+---+",
+                "    . 1 |   |   |   |   |   |   | < | *",
+                "        +---+---+---+---+---+---+---+",
+                "    * 0 | > | ^ |   |   |   |   |   | .",
+                "        +---+---+---+---+---+---+---+",
+                "        |===|   |   |   | ^ | v |===|",
+                "        +---+---+---+---+---+---+---+",
+                "Yellow /      0   1   2   3   4",
+                "      / Red   .   *   :   *   ."].join("\n"));
+        }
+    }
+
+    mod colour_swap {
+        use super::super::Board5;
+        use crate::game::commons::{Turn, Board, Position};
+        use crate::game::rule_set::RuleSet;
+
+        #[test]
+        fn official_rules() {
+            // red and yellow pieces of the same index have different speeds
+            assert!(!RuleSet::<5>::default().is_colour_symmetric());
+            assert_eq!(Board5::new(Turn::Red).get_colour_swapped(), None);
+        }
+
+        #[test]
+        fn symmetric_rules() {
+            let rules = RuleSet {
+                yellow_speeds_outward: [1, 3, 2, 3, 1],
+                yellow_speeds_homeward: [3, 1, 2, 1, 3],
+                ..RuleSet::default()
+            };
+            let mut board = Board5::with_rules(rules);
+            board.red_pieces[1] = Position::Homeward(2);
+
+            let swapped = board.get_colour_swapped().unwrap();
+            assert_eq!(swapped.yellow_pieces[1], Position::Homeward(2));
+            assert_eq!(swapped.turn, Turn::Yellow);
+            assert_eq!(swapped.get_colour_swapped(), Some(board));
+        }
+    }
+}
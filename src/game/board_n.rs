@@ -0,0 +1,560 @@
+use crate::game::commons::{Board, Position, Turn, GameResult, Code, DecodeError, Move, Jump, Key, get_n_ranks, rank_pieces, unrank_pieces};
+use crate::game::rule_set::RuleSet;
+
+/// Square board with N pieces for each player. Red pieces go up and down, yellow pieces go right and left.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BoardN<const N: usize> {
+    pub(crate) red_pieces: [Position; N],
+    pub(crate) yellow_pieces: [Position; N],
+    pub(crate) turn: Turn,
+    pub(crate) rules: RuleSet<N>,
+}
+
+// decoding a code needs rules, so the board is only available for the sizes which have default rules
+impl<const N: usize> Board for BoardN<N> where RuleSet<N>: Default {
+    fn get_board_size() -> usize {
+        N
+    }
+
+    fn get_move(self: &Self, piece_index: usize) -> Option<Move> {
+        let rules = &self.rules;
+        let (pieces_turn, pieces_opposite) = self.get_pieces_of(self.turn);
+
+        let mut jumps = Vec::new();
+        let mut jump = |target_piece_index: usize, target_position: Position| -> bool {
+            let reset_position = match target_position {
+                Position::Outward(m) if m == piece_index as u8 + 1 => Position::Outward(0),
+                Position::Homeward(m) if m == (N - piece_index) as u8 => Position::Homeward(0),
+                _ => return false
+            };
+            jumps.push(Jump { piece_index: target_piece_index, from: target_position, to: reset_position });
+            true
+        };
+
+        let from = pieces_turn[piece_index];
+        let to = match from {
+            Position::Outward(n) => {
+                let base_moves = rules.get_speed_outward(self.turn, piece_index);
+                let mut jumped_previously = false;
+
+                let mut n_moves = 0;
+                let mut path = n;
+                while n_moves < base_moves || jumped_previously {
+                    n_moves += 1;
+
+                    if path >= N as u8 {
+                        // reaches turning point
+                        break;
+                    }
+
+                    let target_piece_index = path as usize;
+                    if jump(target_piece_index, pieces_opposite[target_piece_index]) {
+                        jumped_previously = true;
+                    } else if jumped_previously {
+                        break;
+                    }
+                    path += 1;
+                }
+                if n + n_moves > N as u8 { Position::Homeward(0) } else { Position::Outward(n + n_moves) }
+            }
+            Position::Homeward(n) => {
+                let base_moves = rules.get_speed_homeward(self.turn, piece_index);
+                let mut jumped_previously = false;
+
+                let mut n_moves = 0;
+                let mut path = n;
+                while n_moves < base_moves || jumped_previously {
+                    n_moves += 1;
+
+                    if path >= N as u8 {
+                        // reaches finish point
+                        break;
+                    }
+
+                    let target_piece_index = N - path as usize - 1;
+                    if jump(target_piece_index, pieces_opposite[target_piece_index]) {
+                        jumped_previously = true;
+                    } else if jumped_previously {
+                        break;
+                    }
+                    path += 1;
+                }
+                if n + base_moves > N as u8 { Position::Finished } else { Position::Homeward(n + n_moves) }
+            }
+            Position::Finished => return Option::None
+        };
+
+        Option::Some(Move { turn: self.turn, piece_index, from, to, jumps })
+    }
+
+    fn apply_move(self: &Self, the_move: &Move) -> Option<Self> {
+        let (pieces_turn, pieces_opposite) = self.get_pieces_of(the_move.turn);
+
+        let is_applicable = the_move.turn == self.turn
+            && pieces_turn.get(the_move.piece_index) == Some(&the_move.from)
+            && the_move.jumps.iter().all(|j| pieces_opposite.get(j.piece_index) == Some(&j.from));
+        if !is_applicable {
+            return Option::None;
+        }
+
+        let mut cloned_self = *self;
+
+        let (pieces_turn, pieces_opposite) = if self.turn == Turn::Red {
+            (&mut cloned_self.red_pieces, &mut cloned_self.yellow_pieces)
+        } else {
+            (&mut cloned_self.yellow_pieces, &mut cloned_self.red_pieces)
+        };
+
+        pieces_turn[the_move.piece_index] = the_move.to;
+        for jump in the_move.jumps.iter() {
+            pieces_opposite[jump.piece_index] = jump.to;
+        }
+
+        cloned_self.turn = self.turn.get_opposite();
+
+        Option::Some(cloned_self)
+    }
+
+    fn encode(self: &Self) -> Code {
+        let join = |pieces: &[Position]| pieces.iter().map(|p| p.to_string()).collect::<String>();
+
+        Code(format!("r{}y{}t{}",
+                     join(&self.red_pieces),
+                     join(&self.yellow_pieces),
+                     if self.turn == Turn::Red { "r" } else { "y" }
+        ))
+    }
+
+    fn get_turn_from_code(code: &Code) -> Turn {
+        if code.0.ends_with('r') { Turn::Red } else { Turn::Yellow }
+    }
+
+    fn decode(code: &Code) -> Result<Self, DecodeError> {
+        Self::decode_with_rules(code, RuleSet::default())
+    }
+
+    // bit 0: turn (0 = red), then the index of each red piece and each yellow piece in `BITS_PER_PIECE` bits
+    fn to_key(self: &Self) -> Key {
+        assert!(2 * N * Self::BITS_PER_PIECE < 64, "too large board to pack into a key"); // with the turn bit
+
+        let mut key = if self.turn == Turn::Red { 0 } else { 1 };
+        for (i, p) in self.red_pieces.iter().chain(self.yellow_pieces.iter()).enumerate() {
+            key |= (p.get_index(N) as u64) << (1 + i * Self::BITS_PER_PIECE);
+        }
+
+        Key(key)
+    }
+
+    fn get_turn_from_key(key: &Key) -> Turn {
+        if key.0 & 1 == 0 { Turn::Red } else { Turn::Yellow }
+    }
+
+    fn from_key(key: &Key) -> Result<Self, DecodeError> {
+        Self::from_key_with_rules(key, RuleSet::default())
+    }
+
+    fn rebuild_from_key(self: &Self, key: &Key) -> Result<Self, DecodeError> {
+        Self::from_key_with_rules(key, self.rules)
+    }
+
+    fn get_n_ranks() -> u64 {
+        get_n_ranks(N)
+    }
+
+    fn to_rank(self: &Self) -> Option<u64> {
+        rank_pieces(&self.red_pieces, &self.yellow_pieces, self.turn, N)
+    }
+
+    // mirrored by the diagonal, red piece i goes on the path of yellow piece i and vice versa
+    // Only for the colour-symmetric rules: swapping the speed tables as well would give a board of other rules,
+    // which the same analysis can't store, so the official 5x5 rules get no halving from this.
+    fn get_colour_swapped(self: &Self) -> Option<Self> {
+        if !self.rules.is_colour_symmetric() {
+            return None;
+        }
+
+        Some(BoardN {
+            red_pieces: self.yellow_pieces,
+            yellow_pieces: self.red_pieces,
+            turn: self.turn.get_opposite(),
+            rules: self.rules,
+        })
+    }
+
+    fn get_result(self: &Self) -> GameResult {
+        let is_red_finished = self.get_n_finished(Turn::Red) >= self.rules.pieces_to_win;
+        let is_yellow_finished = self.get_n_finished(Turn::Yellow) >= self.rules.pieces_to_win;
+
+        match (is_red_finished, is_yellow_finished) {
+            (false, false) => GameResult::Unknown,
+            (true, false) => GameResult::RedWins,
+            (false, true) => GameResult::YellowWins,
+            (true, true) => GameResult::Invalid
+        }
+    }
+
+    fn get_rules_line(self: &Self) -> String {
+        format!("{:?}", self.rules)
+    }
+
+    fn get_n_finished(self: &Self, turn: Turn) -> usize {
+        let (pieces, _) = self.get_pieces_of(turn);
+        pieces.iter().filter(|&p| *p == Position::Finished).count()
+    }
+
+    // speed marks: '.' = 1, ':' = 2, '*' = 3
+    //               .   :   .  next-> Yellow
+    //         +---+---+---+---+---+
+    //         |===| v |   | v |===|
+    //         +---+---+---+---+---+
+    //     . 2 | > |   |   |   |   | :
+    //         +---+---+---+---+---+
+    //     : 1 |   |   |   |   | < | .
+    //         +---+---+---+---+---+
+    //     . 0 | > |   |   |   |   | :
+    //         +---+---+---+---+---+
+    //         |===|   | ^ |   |===|
+    //         +---+---+---+---+---+
+    // Yellow /      0   1   2
+    //       / Red   :   .   :
+    fn draw_ascii_art(self: &Self) -> String {
+        const EMPTY: &str = "   ";
+        const UP: &str = " ^ ";
+        const RIGHT: &str = " > ";
+        const DOWN: &str = " v ";
+        const LEFT: &str = " < ";
+
+        let marks = |speeds: &[u8]| -> Vec<String> {
+            speeds.iter().map(|&s| match s {
+                1 => ".".to_string(),
+                2 => ":".to_string(),
+                3 => "*".to_string(),
+                _ => s.to_string(),
+            }).collect()
+        };
+
+        let red_turning = self.red_pieces.iter()
+            .map(|&p| if p == Position::Homeward(0) { DOWN } else { EMPTY })
+            .collect::<Vec<_>>();
+
+        let red_starting = self.red_pieces.iter()
+            .map(|&p| match p {
+                Position::Outward(0) => UP,
+                Position::Finished => DOWN,
+                _ => EMPTY
+            })
+            .collect::<Vec<_>>();
+
+        let board = self;
+        let get_square = |yellow_index: usize, red_index: usize| -> &'static str {
+            match (board.red_pieces[red_index], board.yellow_pieces[yellow_index]) {
+                (Position::Outward(n), _) if n == yellow_index as u8 + 1 => UP,
+                (Position::Homeward(n), _) if n == (N - yellow_index) as u8 => DOWN,
+                (_, Position::Outward(n)) if n == red_index as u8 + 1 => RIGHT,
+                (_, Position::Homeward(n)) if n == (N - red_index) as u8 => LEFT,
+                _ => EMPTY
+            }
+        };
+
+        let yellow_marks_outward = marks(&self.rules.yellow_speeds_outward);
+        let yellow_marks_homeward = marks(&self.rules.yellow_speeds_homeward);
+        let separator = format!("        +{}", "---+".repeat(N + 2));
+
+        let mut lines = vec![
+            format!("              {}  next-> {}", marks(&self.rules.red_speeds_homeward).join("   "), if self.turn == Turn::Red { "Red" } else { "Yellow" }),
+            separator.clone(),
+            format!("        |===|{}|===|", red_turning.join("|")),
+            separator.clone(),
+        ];
+
+        for yellow_index in (0..N).rev() {
+            let yellow_starting = match self.yellow_pieces[yellow_index] {
+                Position::Outward(0) => RIGHT,
+                Position::Finished => LEFT,
+                _ => EMPTY,
+            };
+            let yellow_turning = if self.yellow_pieces[yellow_index] == Position::Homeward(0) { LEFT } else { EMPTY };
+            let squares = (0..N).map(|red_index| get_square(yellow_index, red_index)).collect::<Vec<_>>();
+
+            lines.push(format!("    {} {} |{}|{}|{}| {}",
+                               yellow_marks_outward[yellow_index],
+                               yellow_index,
+                               yellow_starting,
+                               squares.join("|"),
+                               yellow_turning,
+                               yellow_marks_homeward[yellow_index]));
+            lines.push(separator.clone());
+        }
+
+        lines.push(format!("        |===|{}|===|", red_starting.join("|")));
+        lines.push(separator);
+        lines.push(format!("Yellow /      {}", (0..N).map(|i| i.to_string()).collect::<Vec<_>>().join("   ")));
+        lines.push(format!("      / Red   {}", marks(&self.rules.red_speeds_outward).join("   ")));
+
+        return lines.join("\n");
+    }
+}
+
+impl<const N: usize> BoardN<N> where RuleSet<N>: Default {
+    /// Initial board of the official rules.
+    pub fn new(the_first_move: Turn) -> Self {
+        BoardN { turn: the_first_move, ..BoardN::with_rules(RuleSet::default()) }
+    }
+}
+
+impl<const N: usize> BoardN<N> {
+    /// Initial board of any rules, e.g. a variant of speeds or of the board size.
+    pub fn with_rules(rules: RuleSet<N>) -> Self {
+        BoardN {
+            red_pieces: [Position::Outward(0); N],
+            yellow_pieces: [Position::Outward(0); N],
+            turn: rules.first_move,
+            rules,
+        }
+    }
+
+    pub fn get_rules(self: &Self) -> &RuleSet<N> {
+        &self.rules
+    }
+
+    // (pieces of the turn, pieces of the opposite)
+    fn get_pieces_of(self: &Self, turn: Turn) -> (&[Position; N], &[Position; N]) {
+        if turn == Turn::Red { (&self.red_pieces, &self.yellow_pieces) } else { (&self.yellow_pieces, &self.red_pieces) }
+    }
+
+    /// Rebuilds the board from the code of `Board::encode`, e.g. "ro0h1f_yo2o0h4tr".
+    pub fn decode_with_rules(code: &Code, rules: RuleSet<N>) -> Result<Self, DecodeError> {
+        let invalid_format = || DecodeError::Format(code.0.clone());
+
+        let rest = code.0.strip_prefix('r').ok_or_else(invalid_format)?;
+        let (red, rest) = rest.split_at(rest.find('y').ok_or_else(invalid_format)?);
+        let (yellow, turn) = rest[1..].split_at(rest[1..].find('t').ok_or_else(invalid_format)?);
+
+        Ok(BoardN {
+            red_pieces: Self::decode_pieces(code, red)?,
+            yellow_pieces: Self::decode_pieces(code, yellow)?,
+            turn: turn[1..].parse()?,
+            rules,
+        })
+    }
+
+    const BITS_PER_PIECE: usize = (usize::BITS - (Position::get_n_indices(N) - 1).leading_zeros()) as usize;
+
+    /// Rebuilds the board from the key of `Board::to_key`.
+    pub fn from_key_with_rules(key: &Key, rules: RuleSet<N>) -> Result<Self, DecodeError> {
+        if key.0 >> (1 + 2 * N * Self::BITS_PER_PIECE) != 0 {
+            return Err(DecodeError::Key(key.0));
+        }
+
+        let mut board = BoardN::with_rules(rules);
+        board.turn = if key.0 & 1 == 0 { Turn::Red } else { Turn::Yellow };
+
+        let mask = (1 << Self::BITS_PER_PIECE) - 1;
+        for (i, piece) in board.red_pieces.iter_mut().chain(board.yellow_pieces.iter_mut()).enumerate() {
+            let index = (key.0 >> (1 + i * Self::BITS_PER_PIECE)) & mask;
+            *piece = Position::from_index(index as usize, N).ok_or(DecodeError::Key(key.0))?;
+        }
+
+        Ok(board)
+    }
+
+    /// Rebuilds the board from the rank of `Board::to_rank`.
+    pub fn from_rank_with_rules(rank: u64, rules: RuleSet<N>) -> Option<Self> {
+        let (pieces, turn) = unrank_pieces(rank, N)?;
+
+        let mut board = BoardN::with_rules(rules);
+        board.red_pieces.copy_from_slice(&pieces[..N]);
+        board.yellow_pieces.copy_from_slice(&pieces[N..]);
+        board.turn = turn;
+
+        Some(board)
+    }
+
+    fn decode_pieces(code: &Code, s: &str) -> Result<[Position; N], DecodeError> {
+        let starts: Vec<_> = s.match_indices(['o', 'h', 'f']).map(|(i, _)| i).collect();
+        if starts.len() != N || starts.first() != Some(&0) {
+            return Err(DecodeError::Format(code.0.clone()));
+        }
+
+        let mut pieces = [Position::Finished; N];
+        for (i, &start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).cloned().unwrap_or(s.len());
+            let piece = &s[start..end];
+            pieces[i] = match piece.parse()? {
+                // a piece which jumps at the last square stays at Homeward(N + 1) until the next move
+                Position::Outward(n) if n as usize > N => return Err(DecodeError::Position(piece.to_string())),
+                Position::Homeward(n) if n as usize > N + 1 => return Err(DecodeError::Position(piece.to_string())),
+                p => p,
+            };
+        }
+
+        Ok(pieces)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // experimental 4x4 board, which has no official speeds
+    mod board4 {
+        use super::super::BoardN;
+        use crate::game::commons::{Turn, Board, Position, Code};
+        use crate::game::rule_set::RuleSet;
+
+        type Board4 = BoardN<4>;
+
+        impl Default for RuleSet<4> {
+            fn default() -> Self {
+                RuleSet {
+                    red_speeds_outward: [1, 3, 3, 1],
+                    yellow_speeds_outward: [3, 1, 1, 3],
+                    red_speeds_homeward: [3, 1, 1, 3],
+                    yellow_speeds_homeward: [1, 3, 3, 1],
+                    pieces_to_win: 3,
+                    first_move: Turn::Red,
+                }
+            }
+        }
+
+        #[test]
+        fn move_r1o0_then_jump_y0o2_and_y1o2() {
+            let mut board = Board4::new(Turn::Red);
+
+            board.yellow_pieces[0] = Position::Outward(2);
+            board.yellow_pieces[1] = Position::Outward(2);
+
+            let board = board.move_at(1).unwrap();
+
+            assert_eq!(board.turn, Turn::Yellow);
+            assert_eq!(board.red_pieces, [Position::Outward(0), Position::Outward(3), Position::Outward(0), Position::Outward(0)]);
+            assert_eq!(board.yellow_pieces, [Position::Outward(0); 4]);
+        }
+
+        #[test]
+        fn encode() {
+            let mut board = Board4::new(Turn::Yellow);
+
+            board.red_pieces[3] = Position::Homeward(1);
+
+            assert_eq!(board.encode(), Code("ro0o0o0h1yo0o0o0o0ty".to_string()));
+            assert_eq!(Board4::decode(&board.encode()), Ok(board));
+        }
+
+        #[test]
+        fn draw_ascii_art() {
+            let mut board = Board4::new(Turn::Red);
+
+            board.red_pieces[3] = Position::Homeward(1);
+            board.yellow_pieces[2] = Position::Homeward(0);
+
+            assert_eq!(board.draw_ascii_art(), [
+                "              *   .   .   *  next-> Red",
+                "        +---+---+---+---+---+---+",
+                "        |===|   |   |   |   |===|",
+                "        +---+---+---+---+---+---+",
+                "    * 3 | > |   |   |   | v |   | .",
+                "        +---+---+---+---+---+---+",
+                "    . 2 |   |   |   |   |   | < | *",
+                "        +---+---+---+---+---+---+",
+                "    . 1 | > |   |   |   |   |   | *",
+                "        +---+---+---+---+---+---+",
+                "    * 0 | > |   |   |   |   |   | .",
+                "        +---+---+---+---+---+---+",
+                "        |===| ^ | ^ | ^ |   |===|",
+                "        +---+---+---+---+---+---+",
+                "Yellow /      0   1   2   3",
+                "      / Red   .   *   *   ."].join("\n"));
+        }
+    }
+
+    mod rules {
+        use super::super::BoardN;
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Turn, Board, Position, GameResult};
+        use crate::game::rule_set::RuleSet;
+
+        #[test]
+        fn first_move_of_rules() {
+            let board = Board3::with_rules(RuleSet { first_move: Turn::Yellow, ..RuleSet::default() });
+
+            assert_eq!(board.turn, Turn::Yellow);
+        }
+
+        #[test]
+        fn all_pieces_to_win() {
+            let mut board = Board3::with_rules(RuleSet { pieces_to_win: 3, ..RuleSet::default() });
+
+            board.red_pieces[0] = Position::Finished;
+            board.red_pieces[1] = Position::Finished;
+
+            assert_eq!(board.get_result(), GameResult::Unknown);
+
+            board.red_pieces[2] = Position::Finished;
+
+            assert_eq!(board.get_result(), GameResult::RedWins);
+        }
+
+        #[test]
+        fn faster_speeds() {
+            let board = BoardN::<3>::with_rules(RuleSet {
+                red_speeds_outward: [3, 3, 3],
+                ..RuleSet::default()
+            });
+
+            let board = board.move_at(1).unwrap();
+
+            assert_eq!(board.red_pieces, [Position::Outward(0), Position::Outward(3), Position::Outward(0)]);
+        }
+    }
+
+    mod colour_swap {
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Turn, Board, GameResult};
+        use crate::game::rule_set::RuleSet;
+        use std::collections::HashSet;
+
+        fn symmetric_rules() -> RuleSet<3> {
+            RuleSet {
+                yellow_speeds_outward: [2, 1, 2],
+                yellow_speeds_homeward: [1, 2, 1],
+                ..RuleSet::default()
+            }
+        }
+
+        #[test]
+        fn not_for_official_rules() {
+            assert!(!RuleSet::<3>::default().is_colour_symmetric());
+            assert_eq!(Board3::new(Turn::Red).get_colour_swapped(), None);
+        }
+
+        #[test]
+        fn initial_board() {
+            let board = Board3::with_rules(symmetric_rules());
+
+            assert_eq!(board.get_colour_swapped(), Some(Board3 { turn: Turn::Yellow, ..board }));
+        }
+
+        #[test]
+        fn moves_of_swapped_boards_are_swapped() {
+            let mut checked = HashSet::new();
+            let mut stack = vec![Board3::with_rules(symmetric_rules())];
+
+            while let Some(board) = stack.pop() {
+                if checked.len() >= 10_000 || !checked.insert(board.encode()) {
+                    continue;
+                }
+
+                let swapped = board.get_colour_swapped().unwrap();
+                assert_eq!(swapped.get_colour_swapped(), Some(board));
+                assert_eq!(swapped.get_result(), board.get_result().get_colour_swapped());
+
+                if board.get_result() == GameResult::Unknown {
+                    for i in 0..3 {
+                        let next_board = board.move_at(i);
+                        assert_eq!(swapped.move_at(i), next_board.and_then(|b| b.get_colour_swapped()));
+                        stack.extend(next_board);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,16 +1,72 @@
 use std::fmt::Debug;
+use std::sync::OnceLock;
 
 pub trait Board where Self: Sized + Clone + Debug {
     fn get_board_size() -> usize;
 
-    fn move_at(&self, piece_index: usize) -> Option<Self>;
+    /// The move of the piece in the current turn. None if the piece has been finished.
+    fn get_move(&self, piece_index: usize) -> Option<Move>;
+
+    /// None if the move is not of this board.
+    fn apply_move(&self, the_move: &Move) -> Option<Self>;
+
+    /// Moves which the player in the current turn can choose. Empty if the game is over.
+    fn legal_moves(&self) -> Vec<Move> {
+        if self.get_result() != GameResult::Unknown {
+            return Vec::new();
+        }
+
+        (0..Self::get_board_size()).filter_map(|i| self.get_move(i)).collect()
+    }
+
+    fn move_at(&self, piece_index: usize) -> Option<Self> {
+        self.get_move(piece_index).and_then(|m| self.apply_move(&m))
+    }
 
     fn encode(&self) -> Code;
 
     fn get_turn_from_code(code: &Code) -> Turn;
 
+    fn decode(code: &Code) -> Result<Self, DecodeError>;
+
+    /// Bit-packed board for the analysis. Unlike `encode`, it doesn't allocate.
+    fn to_key(&self) -> Key;
+
+    fn get_turn_from_key(key: &Key) -> Turn;
+
+    fn from_key(key: &Key) -> Result<Self, DecodeError>;
+
+    /// Rebuilds the board of the key with the same rules as this board.
+    fn rebuild_from_key(&self, key: &Key) -> Result<Self, DecodeError> {
+        Self::from_key(key)
+    }
+
+    /// Number of the ranks, i.e. `0..get_n_ranks()` covers all legal boards.
+    fn get_n_ranks() -> u64;
+
+    /// Perfect hash of the board, which is an index for the flat storage of the results. None if not legal.
+    fn to_rank(&self) -> Option<u64>;
+
+    /// The board with the colours of the pieces and the turn swapped, whose result is also swapped.
+    /// None if it's not equivalent, i.e. the rules are not symmetric for the colours.
+    /// The official rules of 3x3 and 5x5 are not, so their boards are never stored as the swapped ones.
+    fn get_colour_swapped(&self) -> Option<Self> {
+        None
+    }
+
     fn get_result(&self) -> GameResult;
 
+    /// The rules of the board in a line, e.g. to check the files of a solver are of the same game. Empty if fixed.
+    fn get_rules_line(&self) -> String {
+        String::new()
+    }
+
+    /// Number of the finished pieces of the player, which never decreases in a game.
+    /// 0 for the boards without such pieces, i.e. all boards are in the same layer of `LayeredSolver`.
+    fn get_n_finished(&self, _turn: Turn) -> usize {
+        0
+    }
+
     fn draw_ascii_art(&self) -> String;
 }
 
@@ -32,6 +88,168 @@ impl std::fmt::Display for Position {
     }
 }
 
+impl std::str::FromStr for Position {
+    type Err = DecodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_step = |digits: &str| -> Result<u8, DecodeError> {
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(DecodeError::Position(s.to_string()));
+            }
+            digits.parse().map_err(|_| DecodeError::Position(s.to_string()))
+        };
+
+        match s {
+            "f_" => Ok(Position::Finished),
+            _ if s.starts_with('o') => parse_step(&s[1..]).map(Position::Outward),
+            _ if s.starts_with('h') => parse_step(&s[1..]).map(Position::Homeward),
+            _ => Err(DecodeError::Position(s.to_string()))
+        }
+    }
+}
+
+impl Position {
+    /// Number of the positions of a piece, i.e. Outward(0..=N), Homeward(0..=N + 1) and Finished.
+    pub const fn get_n_indices(board_size: usize) -> usize {
+        2 * board_size + 4
+    }
+
+    /// Serial number of the position in `0..get_n_indices(board_size)`.
+    pub fn get_index(self: &Self, board_size: usize) -> usize {
+        match self {
+            Position::Outward(n) => *n as usize,
+            Position::Homeward(n) => board_size + 1 + *n as usize,
+            Position::Finished => 2 * board_size + 3,
+        }
+    }
+
+    pub fn from_index(index: usize, board_size: usize) -> Option<Position> {
+        match index {
+            _ if index <= board_size => Some(Position::Outward(index as u8)),
+            _ if index < 2 * board_size + 3 => Some(Position::Homeward((index - board_size - 1) as u8)),
+            _ if index == 2 * board_size + 3 => Some(Position::Finished),
+            _ => None
+        }
+    }
+
+    /// Index of the lane, i.e. of the piece of the other colour, whose square the piece is on. None if off the squares.
+    /// The red piece i on lane j and the yellow piece j on lane i share a square.
+    pub fn get_lane(self: &Self, board_size: usize) -> Option<usize> {
+        match *self {
+            Position::Outward(n) if 1 <= n && n as usize <= board_size => Some(n as usize - 1),
+            Position::Homeward(n) if 1 <= n && n as usize <= board_size => Some(board_size - n as usize),
+            _ => None
+        }
+    }
+}
+
+// the largest board whose keys fit in u64, i.e. which can be ranked
+const MAX_RANKED_SIZE: usize = 6;
+
+// first rank / 2 of each red pieces as a mixed radix number, and the number of the ranks / 2 at the end
+fn get_red_offsets(board_size: usize) -> &'static [u64] {
+    static RED_OFFSETS: [OnceLock<Vec<u64>>; MAX_RANKED_SIZE + 1] = [const { OnceLock::new() }; MAX_RANKED_SIZE + 1];
+    assert!(board_size <= MAX_RANKED_SIZE, "too large board to rank");
+
+    RED_OFFSETS[board_size].get_or_init(|| {
+        let radix = Position::get_n_indices(board_size);
+        let n_reds = radix.pow(board_size as u32);
+
+        let mut offsets = Vec::with_capacity(n_reds + 1);
+        let mut offset = 0;
+        let mut red_pieces = vec![Position::Outward(0); board_size];
+        for red_rank in 0..n_reds {
+            let mut rest = red_rank;
+            for piece in red_pieces.iter_mut().rev() {
+                *piece = Position::from_index(rest % radix, board_size).unwrap(); // less than the radix
+                rest /= radix;
+            }
+
+            offsets.push(offset);
+            offset += (0..board_size).map(|j| get_n_yellow_positions(&red_pieces, j, board_size) as u64).product::<u64>();
+        }
+        offsets.push(offset);
+
+        offsets
+    })
+}
+
+// positions of the yellow piece j not on the squares of the red pieces
+fn get_n_yellow_positions(red_pieces: &[Position], j: usize, board_size: usize) -> usize {
+    let n_red_on_lane = red_pieces.iter().filter(|p| p.get_lane(board_size) == Some(j)).count();
+
+    // each red piece on the lane takes a square, which is both outward and homeward for the yellow piece
+    Position::get_n_indices(board_size) - 2 * n_red_on_lane
+}
+
+fn is_on_red_piece(red_pieces: &[Position], j: usize, position: Position, board_size: usize) -> bool {
+    position.get_lane(board_size).is_some_and(|i| red_pieces[i].get_lane(board_size) == Some(j))
+}
+
+/// Number of the boards of the size, i.e. of the ranks by `rank_pieces`.
+pub fn get_n_ranks(board_size: usize) -> u64 {
+    2 * get_red_offsets(board_size).last().unwrap() // never empty
+}
+
+/// Perfect hash of the red pieces, then the yellow pieces, and the turn onto `0..get_n_ranks(board_size)`.
+/// It's a bijection from the legal boards, i.e. where no red piece and yellow piece share a square, reachable or not.
+/// The yellow pieces are ranked among the positions left by the red pieces. None if the pieces share a square.
+pub fn rank_pieces(red_pieces: &[Position], yellow_pieces: &[Position], turn: Turn, board_size: usize) -> Option<u64> {
+    let radix = Position::get_n_indices(board_size) as u64;
+    let red_rank = red_pieces.iter().fold(0, |rank, p| rank * radix + p.get_index(board_size) as u64);
+
+    let mut yellow_rank = 0;
+    for (j, &p) in yellow_pieces.iter().enumerate() {
+        if is_on_red_piece(red_pieces, j, p, board_size) {
+            return None;
+        }
+
+        let index = (0..p.get_index(board_size))
+            .filter(|&k| !is_on_red_piece(red_pieces, j, Position::from_index(k, board_size).unwrap(), board_size)) // less than the index
+            .count();
+        yellow_rank = yellow_rank * get_n_yellow_positions(red_pieces, j, board_size) as u64 + index as u64;
+    }
+
+    let rank = get_red_offsets(board_size)[red_rank as usize] + yellow_rank;
+    Some(rank * 2 + if turn == Turn::Red { 0 } else { 1 })
+}
+
+/// The red pieces, then the yellow pieces, and the turn of the rank. None if out of the range.
+pub fn unrank_pieces(rank: u64, board_size: usize) -> Option<(Vec<Position>, Turn)> {
+    if rank >= get_n_ranks(board_size) {
+        return None;
+    }
+
+    let radix = Position::get_n_indices(board_size);
+    let turn = if rank & 1 == 0 { Turn::Red } else { Turn::Yellow };
+
+    // the last red pieces whose offset is not over the rank
+    let offsets = get_red_offsets(board_size);
+    let red_rank = offsets.partition_point(|&offset| offset <= rank / 2) - 1;
+    let mut rest = red_rank;
+    let mut red_pieces = vec![Position::Finished; board_size];
+    for piece in red_pieces.iter_mut().rev() {
+        *piece = Position::from_index(rest % radix, board_size).unwrap(); // less than the radix
+        rest /= radix;
+    }
+
+    let mut rest = rank / 2 - offsets[red_rank];
+    let mut yellow_pieces = vec![Position::Finished; board_size];
+    for (j, piece) in yellow_pieces.iter_mut().enumerate().rev() {
+        let n_positions = get_n_yellow_positions(&red_pieces, j, board_size) as u64;
+        let index = (rest % n_positions) as usize;
+        rest /= n_positions;
+
+        *piece = (0..radix).map(|k| Position::from_index(k, board_size).unwrap()) // less than the radix
+            .filter(|&p| !is_on_red_piece(&red_pieces, j, p, board_size))
+            .nth(index)
+            .unwrap(); // less than the positions
+    }
+
+    red_pieces.extend(yellow_pieces);
+    Some((red_pieces, turn))
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum GameResult {
     Unknown,
@@ -41,6 +259,38 @@ pub enum GameResult {
     Invalid,
 }
 
+impl GameResult {
+    pub fn get_colour_swapped(self: &Self) -> GameResult {
+        match self {
+            GameResult::RedWins => GameResult::YellowWins,
+            GameResult::YellowWins => GameResult::RedWins,
+            _ => *self,
+        }
+    }
+
+    /// One byte of the result for the files, e.g. `ParallelAnalyzer` and `LayeredSolver`.
+    pub fn to_byte(self: &Self) -> u8 {
+        match self {
+            GameResult::Unknown => 0,
+            GameResult::RedWins => 1,
+            GameResult::YellowWins => 2,
+            GameResult::Undeterminable => 3,
+            GameResult::Invalid => 4,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Option<GameResult> {
+        match byte {
+            0 => Some(GameResult::Unknown),
+            1 => Some(GameResult::RedWins),
+            2 => Some(GameResult::YellowWins),
+            3 => Some(GameResult::Undeterminable),
+            4 => Some(GameResult::Invalid),
+            _ => None
+        }
+    }
+}
+
 impl std::fmt::Display for GameResult {
     fn fmt(self: &Self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{:?}", self) // cheat
@@ -53,6 +303,64 @@ pub enum Turn {
     Yellow,
 }
 
+impl Turn {
+    pub fn get_opposite(self: &Self) -> Turn {
+        match self {
+            Turn::Red => Turn::Yellow,
+            Turn::Yellow => Turn::Red,
+        }
+    }
+}
+
+impl std::fmt::Display for Turn {
+    fn fmt(self: &Self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Turn::Red => write!(f, "r"),
+            Turn::Yellow => write!(f, "y"),
+        }
+    }
+}
+
+impl std::str::FromStr for Turn {
+    type Err = DecodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "r" => Ok(Turn::Red),
+            "y" => Ok(Turn::Yellow),
+            _ => Err(DecodeError::Turn(s.to_string()))
+        }
+    }
+}
+
+/// A piece of the opposite side which is jumped over and sent back.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Jump {
+    pub piece_index: usize,
+    pub from: Position,
+    pub to: Position,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Move {
+    pub turn: Turn,
+    pub piece_index: usize,
+    pub from: Position,
+    pub to: Position,
+    pub jumps: Vec<Jump>,
+}
+
+// e.g. "r1:o0-o3 y0:o2-o0 y1:o2-o0"
+impl std::fmt::Display for Move {
+    fn fmt(self: &Self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}:{}-{}", self.turn, self.piece_index, self.from, self.to)?;
+        for jump in self.jumps.iter() {
+            write!(f, " {}{}:{}-{}", self.turn.get_opposite(), jump.piece_index, jump.from, jump.to)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Code(pub String);
 
@@ -60,4 +368,155 @@ impl Code {
     pub fn get_turn<B>(self: &Self) -> Turn where B: Board {
         B::get_turn_from_code(self)
     }
-}
\ No newline at end of file
+}
+
+/// Bit-packed board, which is used instead of `Code` inside the analysis.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Key(pub u64);
+
+impl Key {
+    pub fn get_turn<B>(self: &Self) -> Turn where B: Board {
+        B::get_turn_from_key(self)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DecodeError {
+    Format(String),
+    Position(String),
+    Turn(String),
+    Key(u64),
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(self: &Self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DecodeError::Format(s) => write!(f, "invalid code format: {}", s),
+            DecodeError::Position(s) => write!(f, "invalid position: {}", s),
+            DecodeError::Turn(s) => write!(f, "invalid turn: {}", s),
+            DecodeError::Key(k) => write!(f, "invalid key: {:#x}", k),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[cfg(test)]
+mod tests {
+    mod position_index {
+        use crate::game::commons::Position;
+
+        #[test]
+        fn all_indices() {
+            let positions: Vec<_> = (0..Position::get_n_indices(3)).map(|i| Position::from_index(i, 3).unwrap()).collect();
+
+            assert_eq!(positions, vec![
+                Position::Outward(0), Position::Outward(1), Position::Outward(2), Position::Outward(3),
+                Position::Homeward(0), Position::Homeward(1), Position::Homeward(2), Position::Homeward(3), Position::Homeward(4),
+                Position::Finished,
+            ]);
+            for (i, p) in positions.iter().enumerate() {
+                assert_eq!(p.get_index(3), i);
+            }
+            assert_eq!(Position::from_index(Position::get_n_indices(3), 3), None);
+        }
+    }
+
+    mod rank {
+        use crate::game::commons::{Position, Turn, get_n_ranks, rank_pieces, unrank_pieces};
+        use std::collections::HashSet;
+
+        // all boards which `Board::decode` accepts, and whether no pieces share a square
+        fn assert_bijective(board_size: usize) {
+            let radix = Position::get_n_indices(board_size);
+            let mut ranks = HashSet::new();
+
+            for index in 0..radix.pow(2 * board_size as u32) {
+                let pieces: Vec<_> = (0..2 * board_size)
+                    .map(|i| Position::from_index(index / radix.pow(i as u32) % radix, board_size).unwrap())
+                    .collect();
+                let (red_pieces, yellow_pieces) = pieces.split_at(board_size);
+                let is_legal = (0..board_size).all(|i| (0..board_size)
+                    .all(|j| red_pieces[i].get_lane(board_size) != Some(j) || yellow_pieces[j].get_lane(board_size) != Some(i)));
+
+                for &turn in &[Turn::Red, Turn::Yellow] {
+                    match rank_pieces(red_pieces, yellow_pieces, turn, board_size) {
+                        Some(rank) => {
+                            assert!(is_legal);
+                            assert!(ranks.insert(rank));
+                            assert_eq!(unrank_pieces(rank, board_size), Some((pieces.clone(), turn)));
+                        }
+                        None => assert!(!is_legal),
+                    }
+                }
+            }
+
+            assert_eq!(ranks.len() as u64, get_n_ranks(board_size));
+            assert_eq!(unrank_pieces(get_n_ranks(board_size), board_size), None);
+        }
+
+        #[test]
+        fn bijective_on_legal_boards() {
+            assert_eq!(get_n_ranks(1), 2 * (4 * 6 + 2 * 4));
+            assert_bijective(1);
+            assert_bijective(2);
+        }
+
+        #[test]
+        fn first_and_last() {
+            assert_eq!(rank_pieces(&[Position::Outward(0); 3], &[Position::Outward(0); 3], Turn::Red, 3), Some(0));
+            assert_eq!(rank_pieces(&[Position::Finished; 3], &[Position::Finished; 3], Turn::Yellow, 3), Some(get_n_ranks(3) - 1));
+        }
+
+        #[test]
+        fn pieces_on_same_square() {
+            // red piece 0 and yellow piece 1 on the square of lane 1 and lane 0
+            let red_pieces = [Position::Outward(2), Position::Outward(0), Position::Outward(0)];
+            let yellow_pieces = [Position::Outward(0), Position::Homeward(3), Position::Outward(0)];
+
+            assert_eq!(rank_pieces(&red_pieces, &yellow_pieces, Turn::Red, 3), None);
+        }
+
+        #[test]
+        fn sizes() {
+            assert_eq!(get_n_ranks(3), 1_336_832);
+            assert_eq!(get_n_ranks(5), 328_650_145_792);
+        }
+    }
+
+    mod from_str {
+        use crate::game::commons::{Position, Turn, DecodeError};
+
+        #[test]
+        fn position() {
+            assert_eq!("o0".parse(), Ok(Position::Outward(0)));
+            assert_eq!("h3".parse(), Ok(Position::Homeward(3)));
+            assert_eq!("o12".parse(), Ok(Position::Outward(12)));
+            assert_eq!("f_".parse(), Ok(Position::Finished));
+        }
+
+        #[test]
+        fn invalid_position() {
+            for s in &["", "o", "h+1", "x1", "f", "o1o2"] {
+                assert_eq!(s.parse::<Position>(), Err(DecodeError::Position(s.to_string())));
+            }
+        }
+
+        #[test]
+        fn turn() {
+            assert_eq!("r".parse(), Ok(Turn::Red));
+            assert_eq!("y".parse(), Ok(Turn::Yellow));
+            assert_eq!("red".parse::<Turn>(), Err(DecodeError::Turn("red".to_string())));
+        }
+
+        #[test]
+        fn round_trip() {
+            for p in &[Position::Outward(2), Position::Homeward(0), Position::Finished] {
+                assert_eq!(p.to_string().parse(), Ok(*p));
+            }
+            for t in &[Turn::Red, Turn::Yellow] {
+                assert_eq!(t.to_string().parse(), Ok(*t));
+            }
+        }
+    }
+}
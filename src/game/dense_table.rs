@@ -0,0 +1,148 @@
+use crate::game::commons::{Board, GameResult};
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+
+// cell: 0 = unknown, 1 = undeterminable, 2 = invalid,
+//       3 + 2 * distance (+ 1 if yellow wins) up to `MAX_DISTANCE`, and the wins farther without the distance
+const UNDETERMINABLE: u8 = 1;
+const INVALID: u8 = 2;
+const WIN: u8 = 3;
+const MAX_DISTANCE: u32 = 124;
+const RED_WINS_FAR: u8 = 253;
+const YELLOW_WINS_FAR: u8 = 254;
+
+/// Results of all legal boards in a flat array indexed by the rank of the board, one byte for each.
+/// The legal boards are 1,336,832 for 3x3, of which 546,696 are reachable, but about 3.3e11 for 5x5.
+pub struct DenseTable<B: Board> {
+    cells: Vec<u8>,
+    _marker: PhantomData<fn() -> B>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum DenseTableError {
+    // number of the bytes which can't be allocated
+    TooLarge(u64),
+}
+
+impl std::fmt::Display for DenseTableError {
+    fn fmt(self: &Self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DenseTableError::TooLarge(n) => write!(f, "too large dense table to allocate: {} bytes", n),
+        }
+    }
+}
+
+impl std::error::Error for DenseTableError {}
+
+impl<B> DenseTable<B> where B: Board {
+    /// The table of `Board::get_n_ranks()` bytes, all of which are unknown. An error if they don't fit in the memory,
+    /// e.g. for 5x5.
+    pub fn new() -> Result<DenseTable<B>, DenseTableError> {
+        allocate(B::get_n_ranks()).map(DenseTable::from_cells)
+    }
+
+    pub fn from_cells(cells: Vec<u8>) -> DenseTable<B> {
+        DenseTable { cells, _marker: PhantomData }
+    }
+
+    pub fn get_cells(self: &Self) -> &[u8] {
+        &self.cells
+    }
+
+    /// Result and distance of the board of the rank. Distance is None if undeterminable, or if too far to store.
+    pub fn get(self: &Self, rank: u64) -> (GameResult, Option<u32>) {
+        decode_cell(self.cells[rank as usize])
+    }
+
+    pub fn set(self: &mut Self, rank: u64, game_result: GameResult, distance: Option<u32>) {
+        self.cells[rank as usize] = encode_cell(game_result, distance);
+    }
+}
+
+fn allocate(n_bytes: u64) -> Result<Vec<u8>, DenseTableError> {
+    let mut cells = Vec::new();
+    usize::try_from(n_bytes).ok()
+        .and_then(|n| cells.try_reserve_exact(n).ok())
+        .ok_or(DenseTableError::TooLarge(n_bytes))?;
+    cells.resize(n_bytes as usize, 0);
+
+    Ok(cells)
+}
+
+/// One byte of the result and the distance, which is shared with the tablebase.
+pub fn encode_cell(game_result: GameResult, distance: Option<u32>) -> u8 {
+    match (game_result, distance) {
+        (GameResult::Unknown, _) => 0,
+        (GameResult::Undeterminable, _) => UNDETERMINABLE,
+        (GameResult::Invalid, _) => INVALID,
+        (GameResult::RedWins, Some(d)) if d <= MAX_DISTANCE => WIN + 2 * d as u8,
+        (GameResult::YellowWins, Some(d)) if d <= MAX_DISTANCE => WIN + 2 * d as u8 + 1,
+        (GameResult::RedWins, _) => RED_WINS_FAR,
+        (GameResult::YellowWins, _) => YELLOW_WINS_FAR,
+    }
+}
+
+pub fn decode_cell(cell: u8) -> (GameResult, Option<u32>) {
+    match cell {
+        0 => (GameResult::Unknown, None),
+        UNDETERMINABLE => (GameResult::Undeterminable, None),
+        INVALID => (GameResult::Invalid, None),
+        RED_WINS_FAR => (GameResult::RedWins, None),
+        YELLOW_WINS_FAR => (GameResult::YellowWins, None),
+        255 => (GameResult::Unknown, None), // not used
+        cell => {
+            let result = if (cell - WIN) & 1 == 0 { GameResult::RedWins } else { GameResult::YellowWins };
+            (result, Some(u32::from(cell - WIN) / 2))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod get_and_set {
+        use super::super::{allocate, DenseTable, DenseTableError};
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Board, GameResult, Turn};
+
+        #[test]
+        fn all_unknown_at_first() {
+            let table = DenseTable::<Board3>::new().unwrap();
+
+            assert_eq!(table.get_cells().len(), 1_336_832);
+            assert_eq!(table.get(Board3::new(Turn::Red).to_rank().unwrap()), (GameResult::Unknown, None));
+        }
+
+        #[test]
+        fn too_large_to_allocate() {
+            assert_eq!(allocate(u64::MAX), Err(DenseTableError::TooLarge(u64::MAX)));
+        }
+
+        #[test]
+        fn round_trip() {
+            let mut table = DenseTable::<Board3>::new().unwrap();
+
+            for &(result, distance) in &[
+                (GameResult::Undeterminable, None),
+                (GameResult::Invalid, None),
+                (GameResult::RedWins, Some(0)),
+                (GameResult::YellowWins, Some(0)),
+                (GameResult::RedWins, Some(47)),
+                (GameResult::YellowWins, Some(124)),
+                (GameResult::RedWins, None),
+                (GameResult::YellowWins, None),
+            ] {
+                table.set(1, result, distance);
+                assert_eq!(table.get(1), (result, distance));
+            }
+        }
+
+        #[test]
+        fn too_far_to_store_the_distance() {
+            let mut table = DenseTable::<Board3>::new().unwrap();
+
+            table.set(1, GameResult::YellowWins, Some(125));
+
+            assert_eq!(table.get(1), (GameResult::YellowWins, None));
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use crate::game::analysis::Analyzer;
+use crate::game::commons::{Board, GameResult, Key};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+// Files are the sorted keys without duplicates, 8 bytes in little endian for each.
+//   layer-<depth>.bin: the boards first found at the depth from the first board
+//   boards.bin: all boards, merged from the layers once the search is done
+//   run-<index>.bin: the sorted part of the next boards of a layer, removed after merged
+//   run-<pass>-<index>.bin: the runs (or the layers for the pass 0) merged by `MAX_FAN_IN` in a pass, removed after merged
+// The files left by an earlier search in the directory are removed before searching.
+
+// runs merged at once, and layers excluded at once, far less than the limit of the open files
+const MAX_FAN_IN: usize = 64;
+
+/// Breadth-first search which keeps the boards in files instead of memory, for the boards more than the memory.
+/// Only `chunk_size` boards are in memory at once, and the layers of the boards are read chunk by chunk.
+pub struct ExternalSearch<B: Board> {
+    directory: PathBuf,
+    chunk_size: usize,
+    _marker: PhantomData<fn() -> B>,
+}
+
+impl<B> ExternalSearch<B> where B: Board {
+    pub fn new(directory: &Path, chunk_size: usize) -> ExternalSearch<B> {
+        ExternalSearch { directory: directory.to_path_buf(), chunk_size, _marker: PhantomData }
+    }
+
+    /// Writes the reachable boards from the first board layer by layer, and returns the number of boards in each layer.
+    /// The finished boards are in the layers but not expanded. The boards are stored as `Analyzer` does,
+    /// i.e. only one of the colour-swapped boards is for the colour-symmetric rules.
+    pub fn search(self: &Self, first_board: &B) -> io::Result<Vec<u64>> {
+        self.search_with_fan_in(first_board, MAX_FAN_IN)
+    }
+
+    // Each layer is written once, and the next boards are excluded by reading the layers before them.
+    fn search_with_fan_in(self: &Self, first_board: &B, fan_in: usize) -> io::Result<Vec<u64>> {
+        std::fs::create_dir_all(&self.directory)?;
+        self.remove_stale_files()?;
+
+        let (first_board, _) = Analyzer::canonicalize(first_board);
+        write_keys(&self.get_layer_path(0), &[first_board.to_key()])?;
+
+        let mut layer_sizes = vec![1];
+
+        loop {
+            let depth = layer_sizes.len() - 1;
+            let runs = self.expand_layer(&first_board, depth)?;
+            let runs = self.merge_runs(runs, fan_in)?;
+
+            let next_layer_path = self.get_layer_path(depth + 1);
+            let layers: Vec<_> = (0..=depth).map(|d| self.get_layer_path(d)).collect();
+            let mut layer_groups = layers.chunks(fan_in);
+            let mut n_boards = merge_keys(&runs, &next_layer_path, layer_groups.next().unwrap())?; // the layer 0 at least
+            for run in runs.iter() {
+                std::fs::remove_file(run)?;
+            }
+            for group in layer_groups {
+                let mut excluded_path = next_layer_path.clone().into_os_string();
+                excluded_path.push(".tmp");
+                n_boards = merge_keys(std::slice::from_ref(&next_layer_path), Path::new(&excluded_path), group)?;
+                std::fs::rename(&excluded_path, &next_layer_path)?;
+            }
+            if n_boards == 0 {
+                std::fs::remove_file(&next_layer_path)?;
+                break;
+            }
+
+            layer_sizes.push(n_boards);
+        }
+
+        self.write_boards(layer_sizes.len(), fan_in)?;
+
+        return Ok(layer_sizes);
+    }
+
+    fn remove_stale_files(self: &Self) -> io::Result<()> {
+        for entry in std::fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            if name.starts_with("layer-") || name.starts_with("run-") || name.starts_with("boards.bin") {
+                std::fs::remove_file(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // merges the layers into the boards, keeping the layers
+    fn write_boards(self: &Self, n_layers: usize, fan_in: usize) -> io::Result<()> {
+        let layers: Vec<_> = (0..n_layers).map(|d| self.get_layer_path(d)).collect();
+        if layers.len() <= fan_in {
+            return merge_keys(&layers, &self.get_boards_path(), &[]).map(|_| ());
+        }
+
+        let mut runs = Vec::new();
+        for (index, group) in layers.chunks(fan_in).enumerate() {
+            let path = self.directory.join(format!("run-0-{}.bin", index));
+            merge_keys(group, &path, &[])?;
+            runs.push(path);
+        }
+        let runs = self.merge_runs(runs, fan_in)?;
+        merge_keys(&runs, &self.get_boards_path(), &[])?;
+        for run in runs.iter() {
+            std::fs::remove_file(run)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get_layer_path(self: &Self, depth: usize) -> PathBuf {
+        self.directory.join(format!("layer-{}.bin", depth))
+    }
+
+    pub fn get_boards_path(self: &Self) -> PathBuf {
+        self.directory.join("boards.bin")
+    }
+
+    /// Chunks of the boards in the layer, in order of the key.
+    pub fn read_layer(self: &Self, depth: usize) -> io::Result<KeyChunks> {
+        KeyChunks::open(&self.get_layer_path(depth), self.chunk_size)
+    }
+
+    /// Chunks of all boards found, in order of the key.
+    pub fn read_boards(self: &Self) -> io::Result<KeyChunks> {
+        KeyChunks::open(&self.get_boards_path(), self.chunk_size)
+    }
+
+    // the next boards of the layer in the sorted runs of up to `chunk_size` boards
+    fn expand_layer(self: &Self, first_board: &B, depth: usize) -> io::Result<Vec<PathBuf>> {
+        let mut runs = Vec::new();
+        let mut next_keys = Vec::with_capacity(self.chunk_size);
+
+        for chunk in self.read_layer(depth)? {
+            for key in chunk? {
+                let board = first_board.rebuild_from_key(&key).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+                if board.get_result() != GameResult::Unknown {
+                    continue;
+                }
+
+                for the_move in board.legal_moves() {
+                    let (next_board, _) = Analyzer::canonicalize(&board.apply_move(&the_move).unwrap()); // legal move
+                    next_keys.push(next_board.to_key());
+                }
+
+                if next_keys.len() >= self.chunk_size {
+                    runs.push(self.write_run(runs.len(), &mut next_keys)?);
+                }
+            }
+        }
+        if !next_keys.is_empty() {
+            runs.push(self.write_run(runs.len(), &mut next_keys)?);
+        }
+
+        return Ok(runs);
+    }
+
+    // merges the runs by `fan_in` runs in passes until `fan_in` runs at most are left
+    fn merge_runs(self: &Self, mut runs: Vec<PathBuf>, fan_in: usize) -> io::Result<Vec<PathBuf>> {
+        let mut pass = 0;
+
+        while runs.len() > fan_in {
+            pass += 1;
+            let mut merged_runs = Vec::new();
+            for (index, group) in runs.chunks(fan_in).enumerate() {
+                let path = self.directory.join(format!("run-{}-{}.bin", pass, index));
+                merge_keys(group, &path, &[])?;
+                for run in group {
+                    std::fs::remove_file(run)?;
+                }
+                merged_runs.push(path);
+            }
+            runs = merged_runs;
+        }
+
+        return Ok(runs);
+    }
+
+    fn write_run(self: &Self, index: usize, keys: &mut Vec<Key>) -> io::Result<PathBuf> {
+        keys.sort_unstable();
+        keys.dedup();
+
+        let path = self.directory.join(format!("run-{}.bin", index));
+        write_keys(&path, keys)?;
+        keys.clear();
+
+        return Ok(path);
+    }
+}
+
+/// Keys of the file read by `chunk_size` keys at most.
+pub struct KeyChunks {
+    reader: BufReader<File>,
+    chunk_size: usize,
+}
+
+impl KeyChunks {
+    pub fn open(path: &Path, chunk_size: usize) -> io::Result<KeyChunks> {
+        Ok(KeyChunks { reader: BufReader::new(File::open(path)?), chunk_size })
+    }
+}
+
+impl Iterator for KeyChunks {
+    type Item = io::Result<Vec<Key>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Vec::new();
+
+        while chunk.len() < self.chunk_size {
+            match read_key(&mut self.reader) {
+                Ok(Some(key)) => chunk.push(key),
+                Ok(None) => break,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        if chunk.is_empty() { None } else { Some(Ok(chunk)) }
+    }
+}
+
+fn write_keys(path: &Path, keys: &[Key]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for key in keys {
+        file.write_all(&key.0.to_le_bytes())?;
+    }
+
+    file.flush()
+}
+
+// None at the end of the file
+fn read_key(reader: &mut impl Read) -> io::Result<Option<Key>> {
+    let mut bytes = [0; 8];
+
+    match reader.read_exact(&mut bytes) {
+        Ok(()) => Ok(Some(Key(u64::from_le_bytes(bytes)))),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// the smallest key of each file with the index of the file, to merge the files
+type Heads = BinaryHeap<Reverse<(Key, usize)>>;
+
+fn open_heads(paths: &[PathBuf]) -> io::Result<(Vec<BufReader<File>>, Heads)> {
+    let mut readers = paths.iter().map(|p| File::open(p).map(BufReader::new)).collect::<io::Result<Vec<_>>>()?;
+    let mut heads = BinaryHeap::new();
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some(key) = read_key(reader)? {
+            heads.push(Reverse((key, i)));
+        }
+    }
+
+    Ok((readers, heads))
+}
+
+// Merges the sorted files into a sorted file without duplicates nor the keys of the excluded sorted files,
+// and returns the number of keys.
+fn merge_keys(paths: &[PathBuf], merged_path: &Path, excluded_paths: &[PathBuf]) -> io::Result<u64> {
+    let (mut readers, mut heads) = open_heads(paths)?;
+    let (mut excluded_readers, mut excluded_heads) = open_heads(excluded_paths)?;
+
+    let mut file = BufWriter::new(File::create(merged_path)?);
+    let mut last_key = None;
+    let mut n_keys = 0;
+
+    while let Some(Reverse((key, i))) = heads.pop() {
+        if let Some(next_key) = read_key(&mut readers[i])? {
+            heads.push(Reverse((next_key, i)));
+        }
+        if last_key == Some(key) {
+            continue;
+        }
+        last_key = Some(key);
+
+        while let Some(&Reverse((excluded, j))) = excluded_heads.peek() {
+            if excluded >= key {
+                break;
+            }
+            excluded_heads.pop();
+            if let Some(next_excluded) = read_key(&mut excluded_readers[j])? {
+                excluded_heads.push(Reverse((next_excluded, j)));
+            }
+        }
+        if excluded_heads.peek().is_some_and(|Reverse((excluded, _))| *excluded == key) {
+            continue;
+        }
+
+        file.write_all(&key.0.to_le_bytes())?;
+        n_keys += 1;
+    }
+
+    file.flush()?;
+
+    return Ok(n_keys);
+}
+
+#[cfg(test)]
+mod tests {
+    mod search {
+        use super::super::{ExternalSearch, KeyChunks};
+        use crate::game::analysis::Analyzer;
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Board, Code, Key};
+        use std::collections::HashSet;
+        use std::path::PathBuf;
+
+        const CODE: &str = "ro3o2f_yf_o3h0tr";
+
+        fn get_directory(name: &str) -> PathBuf {
+            std::env::temp_dir().join(format!("sqdr-external-{}-{}", name, std::process::id()))
+        }
+
+        fn read_all(chunks: impl Iterator<Item=std::io::Result<Vec<Key>>>, chunk_size: usize) -> Vec<Key> {
+            let chunks: Vec<_> = chunks.map(|c| c.unwrap()).collect();
+            assert!(chunks.iter().all(|c| !c.is_empty() && c.len() <= chunk_size));
+            chunks.into_iter().flatten().collect()
+        }
+
+        #[test]
+        fn same_boards_as_analyzer() {
+            let board = Board3::decode(&Code(CODE.to_string())).unwrap();
+            let analyzer = Analyzer::new();
+            analyzer.analyze(&board);
+            let (nodes, _) = analyzer.emit_nodes_and_links();
+            let expected: HashSet<_> = nodes.iter().map(|(code, _)| Board3::decode(&Code(code.clone())).unwrap().to_key()).collect();
+
+            // far less than the boards, to merge many runs
+            let directory = get_directory("analyzer");
+            let search = ExternalSearch::new(&directory, 16);
+            let layer_sizes = search.search(&board).unwrap();
+
+            let boards = read_all(search.read_boards().unwrap(), 16);
+            assert!(boards.windows(2).all(|w| w[0] < w[1]));
+            assert_eq!(boards.iter().copied().collect::<HashSet<_>>(), expected);
+            assert_eq!(layer_sizes.iter().sum::<u64>(), expected.len() as u64);
+
+            let mut found = HashSet::new();
+            for (depth, size) in layer_sizes.iter().enumerate() {
+                let layer = read_all(search.read_layer(depth).unwrap(), 16);
+                assert_eq!(layer.len() as u64, *size);
+                assert!(layer.windows(2).all(|w| w[0] < w[1]));
+                assert!(layer.iter().all(|key| found.insert(*key)));
+            }
+            assert!(!search.get_layer_path(layer_sizes.len()).exists());
+
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+
+        #[test]
+        fn exclude_layers_in_groups() {
+            let board = Board3::decode(&Code(CODE.to_string())).unwrap();
+            let directory = get_directory("groups");
+            let search = ExternalSearch::new(&directory, 16);
+            let layer_sizes = search.search(&board).unwrap();
+            let boards = read_all(search.read_boards().unwrap(), 16);
+
+            // the layers are excluded and merged into the boards by 2 at a time
+            assert!(layer_sizes.len() > 2 * 2);
+            assert_eq!(search.search_with_fan_in(&board, 2).unwrap(), layer_sizes);
+            assert_eq!(read_all(search.read_boards().unwrap(), 16), boards);
+            assert!(std::fs::read_dir(&directory).unwrap().all(|e| !e.unwrap().file_name().to_str().unwrap().starts_with("run-")));
+
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+
+        #[test]
+        fn stale_files() {
+            let board = Board3::decode(&Code(CODE.to_string())).unwrap();
+            let directory = get_directory("stale");
+            let search = ExternalSearch::new(&directory, 16);
+            let layer_sizes = search.search(&board).unwrap();
+            let boards = read_all(search.read_boards().unwrap(), 16);
+
+            // left by a search of another board, and interrupted while merging
+            let stale_layer = search.get_layer_path(layer_sizes.len() + 3);
+            let stale_run = directory.join("run-1-0.bin");
+            for path in [&stale_layer, &stale_run, &search.get_layer_path(1)].iter() {
+                std::fs::write(path, 7u64.to_le_bytes()).unwrap();
+            }
+
+            assert_eq!(search.search(&board).unwrap(), layer_sizes);
+            assert_eq!(read_all(search.read_boards().unwrap(), 16), boards);
+            assert!(!stale_layer.exists());
+            assert!(!stale_run.exists());
+
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+
+        #[test]
+        fn merge_runs_in_passes() {
+            let directory = get_directory("passes");
+            std::fs::create_dir_all(&directory).unwrap();
+            let search = ExternalSearch::<Board3>::new(&directory, 16);
+            let runs: Vec<_> = (0..10).map(|i| {
+                let mut keys: Vec<_> = (0..5).map(|k| Key(k * 7 + i)).collect();
+                search.write_run(i as usize, &mut keys).unwrap()
+            }).collect();
+
+            let merged_runs = search.merge_runs(runs.clone(), 3).unwrap();
+
+            assert_eq!(merged_runs.len(), 2);
+            assert!(runs.iter().all(|run| !run.exists()));
+            let mut keys: Vec<_> = merged_runs.iter()
+                .flat_map(|run| read_all(KeyChunks::open(run, 16).unwrap(), 16))
+                .collect();
+            keys.sort_unstable();
+            keys.dedup();
+            assert_eq!(keys, (0..7 * 4 + 10).map(Key).collect::<Vec<_>>());
+
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+
+        #[test]
+        fn breadth_first() {
+            let board = Board3::decode(&Code(CODE.to_string())).unwrap();
+            let directory = get_directory("breadth");
+            let search = ExternalSearch::new(&directory, 1000);
+            let layer_sizes = search.search(&board).unwrap();
+
+            // each board of the layer is a next board of the previous layer
+            let mut previous_layer = vec![board];
+            for depth in 1..layer_sizes.len() {
+                let next_keys: HashSet<_> = previous_layer.iter()
+                    .flat_map(|b| b.legal_moves().into_iter().map(move |m| b.apply_move(&m).unwrap().to_key()))
+                    .collect();
+                let layer = read_all(search.read_layer(depth).unwrap(), 1000);
+                assert!(layer.iter().all(|key| next_keys.contains(key)));
+                previous_layer = layer.iter().map(|key| Board3::from_key(key).unwrap()).collect();
+            }
+
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+    }
+}
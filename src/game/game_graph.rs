@@ -0,0 +1,216 @@
+use crate::game::commons::{GameResult, Key, Move};
+use std::collections::HashMap;
+
+/// Index of the node in `GameGraph`, in order of the addition.
+pub type NodeIndex = u32;
+
+const MAX_JUMPS: usize = 7;
+
+/// The move from a board to the next board, which is `NodeIndex` in the graph or `Key` before added to it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Edge<T = NodeIndex> {
+    pub piece_index: u8,
+    // number of the jumped pieces in the lowest 4 bits, then the indices in order of the jumps by 4 bits each
+    jumped_pieces: u32,
+    pub next_board: T,
+    // the next board is stored with the colours swapped, so is its result
+    pub colour_swapped: bool,
+}
+
+impl<T> Edge<T> {
+    pub fn new(the_move: &Move, next_board: T, colour_swapped: bool) -> Self {
+        let jumped_pieces: Vec<_> = the_move.jumps.iter().map(|j| j.piece_index).collect();
+        Edge::with_jumps(the_move.piece_index, &jumped_pieces, next_board, colour_swapped)
+    }
+
+    pub fn with_jumps(piece_index: usize, jumped_pieces: &[usize], next_board: T, colour_swapped: bool) -> Self {
+        assert!(Self::can_pack(piece_index, jumped_pieces));
+
+        let packed = jumped_pieces.iter().rev().fold(0, |packed, &i| (packed << 4) | i as u32);
+        Edge { piece_index: piece_index as u8, jumped_pieces: (packed << 4) | jumped_pieces.len() as u32, next_board, colour_swapped }
+    }
+
+    /// Whether the move fits in the edge, e.g. not read from a broken file.
+    pub fn can_pack(piece_index: usize, jumped_pieces: &[usize]) -> bool {
+        piece_index <= usize::from(u8::MAX) && jumped_pieces.len() <= MAX_JUMPS && jumped_pieces.iter().all(|&i| i < 16)
+    }
+
+    pub fn get_jumped_pieces(self: &Self) -> Vec<usize> {
+        let n_jumps = (self.jumped_pieces & 0xf) as usize;
+        (0..n_jumps).map(|i| ((self.jumped_pieces >> (4 * (i + 1))) & 0xf) as usize).collect()
+    }
+
+    pub fn with_next_board<U>(self: &Self, next_board: U) -> Edge<U> {
+        Edge { piece_index: self.piece_index, jumped_pieces: self.jumped_pieces, next_board, colour_swapped: self.colour_swapped }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Node {
+    pub key: Key,
+    pub game_result: GameResult,
+    // plies to the end of the game when both play the best, i.e. the fastest win or the slowest lose
+    pub distance: Option<u32>,
+}
+
+/// The boards and the moves of an analysis, as an arena of the nodes with the edges in flat arrays.
+/// The nodes are expanded in order of the index, i.e. the edges of a node are added after all nodes before it.
+#[derive(Debug)]
+pub struct GameGraph {
+    nodes: Vec<Node>,
+    indices: HashMap<Key, NodeIndex>,
+    // the edges of the node i are edges[edge_offsets[i]..edge_offsets[i + 1]]
+    edge_offsets: Vec<u32>,
+    edges: Vec<Edge>,
+    // (previous node, colour swapped) of the node i are previous_edges[previous_offsets[i]..previous_offsets[i + 1]]
+    previous_offsets: Vec<u32>,
+    previous_edges: Vec<(NodeIndex, bool)>,
+}
+
+impl GameGraph {
+    pub fn new() -> GameGraph {
+        GameGraph {
+            nodes: Vec::new(),
+            indices: HashMap::new(),
+            edge_offsets: vec![0],
+            edges: Vec::new(),
+            previous_offsets: Vec::new(),
+            previous_edges: Vec::new(),
+        }
+    }
+
+    pub fn len(self: &Self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(self: &Self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn get_index(self: &Self, key: &Key) -> Option<NodeIndex> {
+        self.indices.get(key).copied()
+    }
+
+    /// Adds the node if the key is new. Returns the index, and whether it's added.
+    pub fn add_node(self: &mut Self, key: Key, game_result: GameResult, distance: Option<u32>) -> (NodeIndex, bool) {
+        if let Some(index) = self.get_index(&key) {
+            return (index, false);
+        }
+
+        let index = self.nodes.len() as NodeIndex;
+        self.nodes.push(Node { key, game_result, distance });
+        self.indices.insert(key, index);
+
+        return (index, true);
+    }
+
+    pub fn get_node(self: &Self, index: NodeIndex) -> &Node {
+        &self.nodes[index as usize]
+    }
+
+    pub fn get_node_mut(self: &mut Self, index: NodeIndex) -> &mut Node {
+        &mut self.nodes[index as usize]
+    }
+
+    pub fn get_nodes(self: &Self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Number of the nodes whose edges have been added, which are the first ones.
+    pub fn get_n_expanded(self: &Self) -> usize {
+        self.edge_offsets.len() - 1
+    }
+
+    /// Adds the edges of the first node not expanded yet, and returns the index of it.
+    pub fn push_edges(self: &mut Self, edges: impl IntoIterator<Item=Edge>) -> NodeIndex {
+        let index = self.get_n_expanded() as NodeIndex;
+        assert!((index as usize) < self.nodes.len());
+
+        self.edges.extend(edges);
+        self.edge_offsets.push(self.edges.len() as u32);
+
+        return index;
+    }
+
+    /// Empty if the node is not expanded.
+    pub fn get_edges(self: &Self, index: NodeIndex) -> &[Edge] {
+        match (self.edge_offsets.get(index as usize), self.edge_offsets.get(index as usize + 1)) {
+            (Some(&start), Some(&end)) => &self.edges[start as usize..end as usize],
+            _ => &[]
+        }
+    }
+
+    /// Builds the reverse edges of all edges, e.g. before solving.
+    pub fn build_previous_edges(self: &mut Self) {
+        let mut offsets = vec![0; self.nodes.len() + 1];
+        for edge in self.edges.iter() {
+            offsets[edge.next_board as usize + 1] += 1;
+        }
+        for i in 0..self.nodes.len() {
+            offsets[i + 1] += offsets[i];
+        }
+
+        let mut filled = offsets.clone();
+        let mut previous_edges = vec![(0, false); self.edges.len()];
+        for index in 0..self.get_n_expanded() {
+            for edge in self.get_edges(index as NodeIndex) {
+                let position = &mut filled[edge.next_board as usize];
+                previous_edges[*position as usize] = (index as NodeIndex, edge.colour_swapped);
+                *position += 1;
+            }
+        }
+
+        self.previous_offsets = offsets;
+        self.previous_edges = previous_edges;
+    }
+
+    /// (previous node, colour swapped) of the edges to the node, as of `build_previous_edges`.
+    pub fn get_previous_edges(self: &Self, index: NodeIndex) -> &[(NodeIndex, bool)] {
+        match (self.previous_offsets.get(index as usize), self.previous_offsets.get(index as usize + 1)) {
+            (Some(&start), Some(&end)) => &self.previous_edges[start as usize..end as usize],
+            _ => &[]
+        }
+    }
+}
+
+impl Default for GameGraph {
+    fn default() -> Self {
+        GameGraph::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod graph {
+        use super::super::{Edge, GameGraph};
+        use crate::game::commons::{GameResult, Key};
+
+        #[test]
+        fn edges_and_previous_edges() {
+            let mut graph = GameGraph::new();
+            let (a, _) = graph.add_node(Key(10), GameResult::Unknown, None);
+            let (b, _) = graph.add_node(Key(20), GameResult::Unknown, None);
+            let (c, _) = graph.add_node(Key(30), GameResult::RedWins, Some(0));
+            assert_eq!(graph.add_node(Key(20), GameResult::Unknown, None), (b, false));
+
+            assert_eq!(graph.push_edges(vec![Edge::with_jumps(0, &[], b, false), Edge::with_jumps(1, &[], c, true)]), a);
+            assert_eq!(graph.push_edges(vec![Edge::with_jumps(2, &[], c, false)]), b);
+            graph.build_previous_edges();
+
+            assert_eq!(graph.get_n_expanded(), 2);
+            assert_eq!(graph.get_edges(a).iter().map(|e| e.next_board).collect::<Vec<_>>(), vec![b, c]);
+            assert_eq!(graph.get_edges(c), &[]);
+            assert_eq!(graph.get_previous_edges(a), &[]);
+            assert_eq!(graph.get_previous_edges(b), &[(a, false)]);
+            assert_eq!(graph.get_previous_edges(c), &[(a, true), (b, false)]);
+            assert_eq!(graph.get_node(graph.get_index(&Key(30)).unwrap()).distance, Some(0));
+        }
+
+        #[test]
+        fn jumped_pieces_in_order() {
+            for jumps in &[vec![], vec![0], vec![2, 1, 0], vec![4, 3, 2, 1, 0, 1, 2]] {
+                assert_eq!(&Edge::with_jumps(3, jumps, 0, false).get_jumped_pieces(), jumps);
+            }
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use crate::game::analysis::Analyzer;
+use crate::game::commons::{Board, GameResult, Key, Turn};
+use crate::game::external_search::{ExternalSearch, KeyChunks};
+use crate::game::game_graph::{Edge, GameGraph};
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+// (finished pieces of red, finished pieces of yellow), or (more, less) for the colour-symmetric rules
+type Layer = (usize, usize);
+
+// Files in the directory:
+//   search/: the files of `ExternalSearch`, removed after split into the layers
+//   boards-<red>-<yellow>.bin: the sorted keys of the layer
+//   layers.txt: "key <first board>" and "rules <rules>" to check on reuse, then "<red> <yellow>" of each layer in lines,
+//               written after all layers, i.e. the search is done
+//   results-<red>-<yellow>.bin: records of the layer sorted by the key, written when the layer is solved
+// Record: key (u64), result (u8, `GameResult::to_byte`), distance (u32, `NO_DISTANCE` if none), in little endian.
+const RECORD_SIZE: u64 = 8 + 1 + 4;
+const NO_DISTANCE: u32 = u32::MAX;
+
+/// Solver which splits the boards into the layers by the number of finished pieces, which never decrease.
+/// The layers of more finished pieces are solved first, so only a layer and its next boards are in memory at once.
+/// The results are the same as `Analyzer`, and the solved layers are kept in the files to continue when stopped.
+pub struct LayeredSolver<B: Board> {
+    directory: PathBuf,
+    chunk_size: usize,
+    _marker: PhantomData<fn() -> B>,
+}
+
+impl<B> LayeredSolver<B> where B: Board {
+    pub fn new(directory: &Path, chunk_size: usize) -> LayeredSolver<B> {
+        LayeredSolver { directory: directory.to_path_buf(), chunk_size, _marker: PhantomData }
+    }
+
+    /// Solves all boards reachable from the first board, and returns the result and the distance of it.
+    /// The search and the solved layers are skipped if they have been done in the directory.
+    pub fn solve(self: &Self, first_board: &B) -> io::Result<(GameResult, Option<u32>)> {
+        std::fs::create_dir_all(&self.directory)?;
+
+        let mut layers = match self.read_layers(first_board)? {
+            Some(layers) => layers,
+            None => self.search(first_board)?
+        };
+        // the next boards are in the same layer or in a layer of one more finished piece
+        layers.sort_by_key(|(red, yellow)| std::cmp::Reverse(red + yellow));
+
+        for layer in layers {
+            if self.get_results_path(layer).exists() {
+                continue;
+            }
+
+            eprintln!("Start solving layer {:?}.", layer);
+            self.solve_layer(first_board, layer)?;
+        }
+
+        return self.probe(first_board)?.ok_or_else(|| io::Error::new(ErrorKind::NotFound, "the first board is not solved"));
+    }
+
+    /// Result and distance of the board in the solved layer. None if the board is not found.
+    pub fn probe(self: &Self, board: &B) -> io::Result<Option<(GameResult, Option<u32>)>> {
+        let (board, colour_swapped) = Analyzer::canonicalize(board);
+        let mut file = match File::open(self.get_results_path(Self::get_layer(&board))) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        // binary search in the records sorted by the key
+        let key = board.to_key();
+        let (mut low, mut high) = (0, file.metadata()?.len() / RECORD_SIZE);
+        while low < high {
+            let middle = (low + high) / 2;
+            file.seek(SeekFrom::Start(middle * RECORD_SIZE))?;
+            let (middle_key, result, distance) = read_record(&mut file)?.unwrap(); // in the file
+
+            if middle_key == key {
+                return Ok(Some((if colour_swapped { result.get_colour_swapped() } else { result }, distance)));
+            } else if middle_key < key {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        return Ok(None);
+    }
+
+    fn get_layer(board: &B) -> Layer {
+        let layer = (board.get_n_finished(Turn::Red), board.get_n_finished(Turn::Yellow));
+
+        // the swapped board is in the swapped layer, which has to be solved together
+        if board.get_colour_swapped().is_some() && layer.0 < layer.1 { (layer.1, layer.0) } else { layer }
+    }
+
+    fn get_boards_path(self: &Self, layer: Layer) -> PathBuf {
+        self.directory.join(format!("boards-{}-{}.bin", layer.0, layer.1))
+    }
+
+    fn get_results_path(self: &Self, layer: Layer) -> PathBuf {
+        self.directory.join(format!("results-{}-{}.bin", layer.0, layer.1))
+    }
+
+    fn get_layers_path(self: &Self) -> PathBuf {
+        self.directory.join("layers.txt")
+    }
+
+    fn get_header(first_board: &B) -> [String; 2] {
+        [format!("key {:#x}", first_board.to_key().0), format!("rules {}", first_board.get_rules_line())]
+    }
+
+    // the files of another first board or rules are not reused, but an error to remove them
+    fn read_layers(self: &Self, first_board: &B) -> io::Result<Option<Vec<Layer>>> {
+        let file = match File::open(self.get_layers_path()) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut lines = BufReader::new(file).lines();
+        for expected in Self::get_header(first_board).iter() {
+            match lines.next().transpose()? {
+                Some(line) if line == *expected => {}
+                line => return Err(io::Error::new(ErrorKind::InvalidData,
+                    format!("{} is of another game ({}, expected {}), remove {} to solve again",
+                        self.get_layers_path().display(), line.unwrap_or_default(), expected, self.directory.display()))),
+            }
+        }
+
+        let mut layers = Vec::new();
+        for line in lines {
+            let line = line?;
+            let counts: Vec<usize> = line.split(' ').map(|s| s.parse()).collect::<Result<_, _>>()
+                .map_err(|_| io::Error::new(ErrorKind::InvalidData, format!("invalid layer: {}", line)))?;
+            match counts[..] {
+                [red, yellow] => layers.push((red, yellow)),
+                _ => return Err(io::Error::new(ErrorKind::InvalidData, format!("invalid layer: {}", line))),
+            }
+        }
+
+        return Ok(Some(layers));
+    }
+
+    // searches all boards by `ExternalSearch`, and splits them into the layers
+    fn search(self: &Self, first_board: &B) -> io::Result<Vec<Layer>> {
+        let search_directory = self.directory.join("search");
+        let search = ExternalSearch::new(&search_directory, self.chunk_size);
+        search.search(first_board)?;
+
+        // the boards are sorted, so are the boards of each layer
+        let mut files: BTreeMap<Layer, BufWriter<File>> = BTreeMap::new();
+        for chunk in search.read_boards()? {
+            for key in chunk? {
+                let layer = Self::get_layer(&Self::rebuild(first_board, &key)?);
+                let file = match files.entry(layer) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(BufWriter::new(File::create(self.get_boards_path(layer))?)),
+                };
+                file.write_all(&key.0.to_le_bytes())?;
+            }
+        }
+
+        let mut layers_file = BufWriter::new(File::create(self.get_layers_path())?);
+        for line in Self::get_header(first_board).iter() {
+            writeln!(layers_file, "{}", line)?;
+        }
+        for (layer, file) in files.iter_mut() {
+            file.flush()?;
+            writeln!(layers_file, "{} {}", layer.0, layer.1)?;
+        }
+        layers_file.flush()?;
+
+        std::fs::remove_dir_all(&search_directory)?;
+
+        return Ok(files.keys().copied().collect());
+    }
+
+    // solves the boards of the layer by `Analyzer` with the solved next boards out of the layer
+    fn solve_layer(self: &Self, first_board: &B, layer: Layer) -> io::Result<()> {
+        let mut keys = Vec::new();
+        for chunk in KeyChunks::open(&self.get_boards_path(layer), self.chunk_size)? {
+            keys.extend(chunk?);
+        }
+
+        // the boards of the layer are the first nodes, and their edges are added after the next boards
+        let mut graph = GameGraph::new();
+        let mut layer_edges = Vec::with_capacity(keys.len());
+        let mut next_boards_out_of_layer: BTreeMap<Layer, Vec<Key>> = BTreeMap::new();
+
+        for key in keys.iter() {
+            let board = Self::rebuild(first_board, key)?;
+            let mut edges = Vec::new();
+
+            for the_move in board.legal_moves() {
+                let (next_board, colour_swapped) = Analyzer::canonicalize(&board.apply_move(&the_move).unwrap()); // legal move
+                let next_layer = Self::get_layer(&next_board);
+                if next_layer != layer {
+                    next_boards_out_of_layer.entry(next_layer).or_default().push(next_board.to_key());
+                }
+
+                edges.push(Edge::new(&the_move, next_board.to_key(), colour_swapped));
+            }
+
+            let game_result = board.get_result();
+            let distance = if game_result == GameResult::RedWins || game_result == GameResult::YellowWins { Some(0) } else { None };
+            graph.add_node(*key, game_result, distance);
+            layer_edges.push(edges);
+        }
+
+        // the results of the next boards, sorted as well as the results files, are read by merging
+        for (next_layer, mut next_keys) in next_boards_out_of_layer {
+            next_keys.sort_unstable();
+            next_keys.dedup();
+
+            let mut results = BufReader::new(File::open(self.get_results_path(next_layer))?);
+            for next_key in next_keys {
+                loop {
+                    match read_record(&mut results)? {
+                        Some((key, game_result, distance)) if key == next_key => {
+                            graph.add_node(key, game_result, distance);
+                            break;
+                        }
+                        Some((key, _, _)) if key < next_key => continue,
+                        _ => return Err(io::Error::new(ErrorKind::InvalidData, format!("next board not solved: {:#x}", next_key.0))),
+                    }
+                }
+            }
+        }
+
+        for edges in layer_edges {
+            let edges: Vec<_> = edges.iter().map(|e| e.with_next_board(graph.get_index(&e.next_board).unwrap())).collect(); // in the layer or solved
+            graph.push_edges(edges);
+        }
+
+        let analyzer = Analyzer::from_graph(graph, Some(first_board.clone()));
+        analyzer.solve_retrograde(None).unwrap(); // never fails without the checkpoint
+
+        // written to a temporary file first, as the file means the layer is solved
+        let mut temporary_path = self.get_results_path(layer).into_os_string();
+        temporary_path.push(".tmp");
+        let mut file = BufWriter::new(File::create(&temporary_path)?);
+        for key in keys.iter() {
+            let (game_result, distance) = analyzer.get_solved(key).unwrap(); // in the graph
+            file.write_all(&key.0.to_le_bytes())?;
+            file.write_all(&[game_result.to_byte()])?;
+            file.write_all(&distance.unwrap_or(NO_DISTANCE).to_le_bytes())?;
+        }
+        file.flush()?;
+        drop(file);
+        std::fs::rename(&temporary_path, self.get_results_path(layer))?;
+
+        Ok(())
+    }
+
+    fn rebuild(first_board: &B, key: &Key) -> io::Result<B> {
+        first_board.rebuild_from_key(key).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+// None at the end of the file
+fn read_record(reader: &mut impl Read) -> io::Result<Option<(Key, GameResult, Option<u32>)>> {
+    let mut bytes = [0; RECORD_SIZE as usize];
+    match reader.read_exact(&mut bytes) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let key = Key(u64::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]));
+    let game_result = GameResult::from_byte(bytes[8]).ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "invalid result"))?;
+    let distance = match u32::from_le_bytes([bytes[9], bytes[10], bytes[11], bytes[12]]) {
+        NO_DISTANCE => None,
+        distance => Some(distance),
+    };
+
+    return Ok(Some((key, game_result, distance)));
+}
+
+#[cfg(test)]
+mod tests {
+    mod solve {
+        use super::super::LayeredSolver;
+        use crate::game::analysis::Analyzer;
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Board, Code, GameResult, Turn};
+        use crate::game::rule_set::RuleSet;
+        use std::io::ErrorKind;
+        use std::path::PathBuf;
+
+        const CODE: &str = "ro3o2f_yf_o3h0tr";
+
+        fn get_directory(name: &str) -> PathBuf {
+            std::env::temp_dir().join(format!("sqdr-layered-{}-{}", name, std::process::id()))
+        }
+
+        fn assert_same_as_analyzer(board: &Board3, name: &str) {
+            let analyzer = Analyzer::new();
+            let result = analyzer.analyze(board);
+
+            let directory = get_directory(name);
+            let solver = LayeredSolver::new(&directory, 64);
+            assert_eq!(solver.solve(board).unwrap(), (result, analyzer.get_distance(&board.encode())));
+
+            let (nodes, _) = analyzer.emit_nodes_and_links();
+            for code in nodes.iter().map(|(code, _)| Code(code.clone())) {
+                let board = Board3::decode_with_rules(&code, *board.get_rules()).unwrap();
+                assert_eq!(solver.probe(&board).unwrap(), analyzer.get_solved(&board.to_key()));
+            }
+
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+
+        #[test]
+        fn same_as_analyzer() {
+            assert_same_as_analyzer(&Board3::decode(&Code(CODE.to_string())).unwrap(), "analyzer");
+        }
+
+        #[test]
+        fn colour_symmetric_rules() {
+            let rules = RuleSet { yellow_speeds_outward: [2, 1, 2], yellow_speeds_homeward: [1, 2, 1], ..RuleSet::default() };
+
+            assert_same_as_analyzer(&Board3::decode_with_rules(&Code(CODE.to_string()), rules).unwrap(), "symmetric");
+        }
+
+        #[test]
+        fn continue_from_solved_layers() {
+            let board = Board3::decode(&Code(CODE.to_string())).unwrap();
+            let directory = get_directory("continue");
+            let solver = LayeredSolver::new(&directory, 64);
+            let solved = solver.solve(&board).unwrap();
+
+            // only the layer of the first board is solved again, so the boards of the other layers are not needed
+            let layer = LayeredSolver::get_layer(&board);
+            std::fs::remove_file(solver.get_results_path(layer)).unwrap();
+            for (red, yellow) in solver.read_layers(&board).unwrap().unwrap() {
+                if (red, yellow) != layer {
+                    std::fs::remove_file(solver.get_boards_path((red, yellow))).unwrap();
+                }
+            }
+
+            assert_eq!(solver.solve(&board).unwrap(), solved);
+            assert_eq!(solved, (GameResult::YellowWins, Some(8)));
+
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+
+        #[test]
+        fn another_game_in_directory() {
+            let board = Board3::decode(&Code(CODE.to_string())).unwrap();
+            let directory = get_directory("another");
+            let solver = LayeredSolver::new(&directory, 64);
+            solver.solve(&board).unwrap();
+
+            let other_board = Board3::decode(&Code("ro3o1f_yf_o2h0ty".to_string())).unwrap();
+            let rules = RuleSet { pieces_to_win: 3, ..RuleSet::default() };
+            let other_rules = Board3::decode_with_rules(&Code(CODE.to_string()), rules).unwrap();
+            for other in [other_board, other_rules].iter() {
+                let e = solver.solve(other).unwrap_err();
+                assert_eq!(e.kind(), ErrorKind::InvalidData);
+                assert!(e.to_string().contains("is of another game"));
+            }
+            assert_eq!(solver.solve(&board).unwrap(), (GameResult::YellowWins, Some(8)));
+
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+
+        #[test]
+        #[ignore] // full 3x3, run with `cargo test --release layered -- --ignored`
+        fn initial_board() {
+            let directory = get_directory("initial");
+            let solver = LayeredSolver::new(&directory, 1 << 20);
+
+            assert_eq!(solver.solve(&Board3::new(Turn::Red)).unwrap(), (GameResult::YellowWins, Some(46)));
+
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+    }
+}
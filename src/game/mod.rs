@@ -1,3 +1,15 @@
+pub mod board_n;
 pub mod board3;
+pub mod board5;
+pub mod rule_set;
 pub mod analysis;
 pub mod commons;
+pub mod dense_table;
+pub mod tablebase;
+pub mod parallel_analysis;
+pub mod external_search;
+pub mod layered_solver;
+pub mod game_graph;
+pub mod play;
+pub mod protocol;
+pub mod server;
@@ -0,0 +1,353 @@
+use crate::game::analysis::Analyzer;
+use crate::game::commons::{Board, Code, GameResult, Key};
+use crate::game::game_graph::{Edge, GameGraph};
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+// cell: the result in the upper 32 bits, and the distance + 1 (0 if none) in the lower 32 bits
+fn pack_cell(game_result: GameResult, distance: Option<u32>) -> u64 {
+    (u64::from(game_result.to_byte()) << 32) | distance.map_or(0, |d| u64::from(d) + 1)
+}
+
+fn unpack_cell(cell: u64) -> (GameResult, Option<u32>) {
+    let game_result = GameResult::from_byte((cell >> 32) as u8).unwrap(); // packed by `pack_cell`
+    let distance = (cell as u32).checked_sub(1);
+
+    (game_result, distance)
+}
+
+struct ParallelNode {
+    cell: AtomicU64,
+    next_boards: Vec<Edge<Key>>,
+}
+
+impl ParallelNode {
+    fn new(game_result: GameResult) -> Self {
+        let distance = match game_result {
+            GameResult::RedWins | GameResult::YellowWins => Some(0),
+            _ => None
+        };
+
+        ParallelNode { cell: AtomicU64::new(pack_cell(game_result, distance)), next_boards: Vec::new() }
+    }
+
+    fn get(self: &Self) -> (GameResult, Option<u32>) {
+        unpack_cell(self.cell.load(Ordering::SeqCst))
+    }
+}
+
+/// The thread-safe variant of `Analyzer`, which expands the boards and propagates the results on all cores.
+/// The results are the same as `Analyzer`, and `into_analyzer` gives the other queries and the exports.
+pub struct ParallelAnalyzer<B: Board> {
+    map: DashMap<Key, ParallelNode>,
+    sample_board: Mutex<Option<B>>,
+}
+
+impl<B> Default for ParallelAnalyzer<B> where B: Board + Send + Sync {
+    fn default() -> Self {
+        ParallelAnalyzer::new()
+    }
+}
+
+impl<B> ParallelAnalyzer<B> where B: Board + Send + Sync {
+    pub fn new() -> ParallelAnalyzer<B> {
+        ParallelAnalyzer { map: DashMap::new(), sample_board: Mutex::new(None) }
+    }
+
+    pub fn analyze(self: &Self, board: &B) -> GameResult {
+        eprintln!("Start searching leaves.");
+
+        self.search(board);
+
+        eprintln!("Finish searching leaves.");
+
+        eprintln!("Start solving.");
+
+        self.solve_retrograde();
+
+        eprintln!("Finish solving.");
+
+        return self.get_solved(&board.to_key()).map_or(GameResult::Unknown, |(result, _)| result);
+    }
+
+    pub fn get_distance(self: &Self, board_code: &Code) -> Option<u32> {
+        let board_key = B::decode(board_code).ok()?.to_key();
+        self.get_solved(&board_key).and_then(|(_, distance)| distance)
+    }
+
+    /// The serial analyzer of the same graph, to query the best moves or to export.
+    pub fn into_analyzer(self: Self) -> Analyzer<B> {
+        let nodes: Vec<_> = self.map.into_iter().collect();
+        let mut graph = GameGraph::new();
+        for (key, node) in nodes.iter() {
+            let (game_result, distance) = node.get();
+            graph.add_node(*key, game_result, distance);
+        }
+        for (_, node) in nodes.iter() {
+            let edges: Vec<_> = node.next_boards.iter().map(|e| e.with_next_board(graph.get_index(&e.next_board).unwrap())).collect();
+            graph.push_edges(edges);
+        }
+
+        Analyzer::from_graph(graph, self.sample_board.into_inner().unwrap())
+    }
+
+    fn get_solved(self: &Self, board_key: &Key) -> Option<(GameResult, Option<u32>)> {
+        if let Some(node) = self.map.get(board_key) {
+            return Some(node.get());
+        }
+
+        let board = self.sample_board.lock().unwrap().as_ref()?.rebuild_from_key(board_key).ok()?;
+        let swapped_key = board.get_colour_swapped()?.to_key();
+        self.map.get(&swapped_key).map(|node| {
+            let (result, distance) = node.get();
+            (result.get_colour_swapped(), distance)
+        })
+    }
+
+    // breadth first, where the boards of each depth are expanded in parallel
+    fn search(self: &Self, first_board: &B) {
+        self.sample_board.lock().unwrap().replace(first_board.clone());
+
+        let (first_board, _) = Analyzer::canonicalize(first_board);
+        let mut boards_to_expand = Vec::new();
+        if let Entry::Vacant(entry) = self.map.entry(first_board.to_key()) {
+            if entry.insert(ParallelNode::new(first_board.get_result())).get().0 == GameResult::Unknown {
+                boards_to_expand.push(first_board);
+            }
+        }
+
+        while !boards_to_expand.is_empty() {
+            boards_to_expand = boards_to_expand.par_iter()
+                .flat_map_iter(|board| {
+                    let mut next_boards_to_expand = Vec::new();
+                    let mut edges = Vec::new();
+
+                    for the_move in board.legal_moves() {
+                        let (next_board, colour_swapped) = Analyzer::canonicalize(&board.apply_move(&the_move).unwrap()); // legal move
+                        let next_board_key = next_board.to_key();
+
+                        // the shard is locked only until inserted, and the other thread finding the same board skips it
+                        if let Entry::Vacant(entry) = self.map.entry(next_board_key) {
+                            if entry.insert(ParallelNode::new(next_board.get_result())).get().0 == GameResult::Unknown {
+                                next_boards_to_expand.push(next_board);
+                            }
+                        }
+
+                        edges.push(Edge::new(&the_move, next_board_key, colour_swapped));
+                    }
+
+                    self.map.get_mut(&board.to_key()).unwrap().next_boards = edges;
+
+                    next_boards_to_expand
+                })
+                .collect();
+        }
+    }
+
+    // Same as `Analyzer`, but the boards of each distance are propagated in parallel.
+    // The wins are propagated before the loses of the same distance, as a winning next board is preferred
+    // even if all next boards are decided at once. Each board is decided by a compare-and-swap from unknown.
+    fn solve_retrograde(self: &Self) {
+        let keys: Vec<Key> = self.map.iter().map(|entry| *entry.key()).collect();
+        let indices: HashMap<Key, u32> = keys.par_iter().enumerate().map(|(i, key)| (*key, i as u32)).collect();
+        let cells: Vec<AtomicU64> = keys.par_iter().map(|key| AtomicU64::new(self.map.get(key).unwrap().cell.load(Ordering::SeqCst))).collect();
+        let is_unknown = |i: u32| unpack_cell(cells[i as usize].load(Ordering::SeqCst)).0 == GameResult::Unknown;
+
+        // (next board, previous board, colour swapped), sorted by the next board to index the previous boards
+        let mut links: Vec<(u32, u32, bool)> = keys.par_iter().enumerate()
+            .filter(|&(i, _)| is_unknown(i as u32))
+            .flat_map_iter(|(i, key)| {
+                let node = self.map.get(key).unwrap();
+                node.next_boards.iter().map(|e| (indices[&e.next_board], i as u32, e.colour_swapped)).collect::<Vec<_>>()
+            })
+            .collect();
+        links.par_sort_unstable();
+        let mut link_offsets = vec![0; keys.len() + 1];
+        for &(next, _, _) in links.iter() {
+            link_offsets[next as usize + 1] += 1;
+        }
+        for i in 0..keys.len() {
+            link_offsets[i + 1] += link_offsets[i];
+        }
+
+        let n_undecided_next_boards: Vec<AtomicU32> = keys.par_iter().enumerate()
+            .map(|(i, key)| AtomicU32::new(if is_unknown(i as u32) { self.map.get(key).unwrap().next_boards.len() as u32 } else { 0 }))
+            .collect();
+
+        // decided boards by the distance, including the ones decided in previous analyses
+        // keyed by `Analyzer::get_propagation_key`, so the unknown distance, e.g. of a capped one, is propagated the last
+        let mut decided_boards: BTreeMap<(bool, Option<u32>), Vec<u32>> = BTreeMap::new();
+        for (i, key) in keys.iter().enumerate() {
+            let i = i as u32;
+            match unpack_cell(cells[i as usize].load(Ordering::SeqCst)) {
+                (GameResult::RedWins, distance) | (GameResult::YellowWins, distance) =>
+                    decided_boards.entry(Analyzer::<B>::get_propagation_key(distance)).or_default().push(i),
+                // no moves means it loses
+                (GameResult::Unknown, _) if n_undecided_next_boards[i as usize].load(Ordering::SeqCst) == 0 => {
+                    cells[i as usize].store(pack_cell(Analyzer::<B>::get_win_of(key.get_turn::<B>().get_opposite()), Some(0)), Ordering::SeqCst);
+                    decided_boards.entry(Analyzer::<B>::get_propagation_key(Some(0))).or_default().push(i);
+                }
+                _ => {}
+            }
+        }
+
+        let decide = |i: u32, game_result: GameResult, distance: Option<u32>| cells[i as usize]
+            .compare_exchange(pack_cell(GameResult::Unknown, None), pack_cell(game_result, distance), Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok();
+        // (previous board, result of the board for the previous board) of the board
+        let previous_boards = |i: u32| {
+            let result = unpack_cell(cells[i as usize].load(Ordering::SeqCst)).0;
+            links[link_offsets[i as usize]..link_offsets[i as usize + 1]].iter()
+                .map(move |&(_, previous, colour_swapped)| (previous, if colour_swapped { result.get_colour_swapped() } else { result }))
+        };
+
+        while let Some(((_, distance), boards)) = decided_boards.pop_first() {
+            let distance = distance.map(|d| d + 1);
+            let winning_boards: Vec<u32> = boards.par_iter()
+                .flat_map_iter(|&i| previous_boards(i)
+                    .filter(|&(previous, result)| result == Analyzer::<B>::get_win_of(keys[previous as usize].get_turn::<B>()) && decide(previous, result, distance))
+                    .collect::<Vec<_>>())
+                .map(|(previous, _)| previous)
+                .collect();
+
+            let losing_boards: Vec<u32> = boards.par_iter()
+                .flat_map_iter(|&i| previous_boards(i)
+                    .filter(|&(previous, result)| result != Analyzer::<B>::get_win_of(keys[previous as usize].get_turn::<B>())
+                        && n_undecided_next_boards[previous as usize].fetch_sub(1, Ordering::SeqCst) == 1
+                        && decide(previous, result, distance))
+                    .collect::<Vec<_>>())
+                .map(|(previous, _)| previous)
+                .collect();
+
+            if !winning_boards.is_empty() || !losing_boards.is_empty() {
+                let next_decided_boards = decided_boards.entry(Analyzer::<B>::get_propagation_key(distance)).or_default();
+                next_decided_boards.extend(winning_boards);
+                next_decided_boards.extend(losing_boards);
+            }
+        }
+
+        keys.par_iter().enumerate().for_each(|(i, key)| {
+            let (game_result, distance) = match unpack_cell(cells[i].load(Ordering::SeqCst)) {
+                (GameResult::Unknown, _) => (GameResult::Undeterminable, None),
+                decided => decided
+            };
+            self.map.get(key).unwrap().cell.store(pack_cell(game_result, distance), Ordering::SeqCst);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod same_as_serial {
+        use super::super::{pack_cell, ParallelAnalyzer, ParallelNode};
+        use crate::game::analysis::Analyzer;
+        use crate::game::game_graph::GameGraph;
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Board, Code, GameResult, Turn};
+        use crate::game::rule_set::RuleSet;
+        use std::sync::atomic::AtomicU64;
+
+        fn assert_same_as_serial(boards: &[Board3]) {
+            let serial = Analyzer::new();
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+            let parallel = ParallelAnalyzer::new();
+
+            for board in boards {
+                assert_eq!(pool.install(|| parallel.analyze(board)), serial.analyze(board));
+            }
+
+            let (nodes, mut links) = serial.emit_nodes_and_links();
+            for code in nodes.iter().map(|(code, _)| Code(code.clone())) {
+                assert_eq!(parallel.get_distance(&code), serial.get_distance(&code));
+            }
+
+            let parallel = parallel.into_analyzer();
+            let (mut parallel_nodes, mut parallel_links) = parallel.emit_nodes_and_links();
+            let mut nodes = nodes;
+            nodes.sort();
+            parallel_nodes.sort();
+            links.sort();
+            parallel_links.sort();
+            assert_eq!(parallel_nodes, nodes);
+            assert_eq!(parallel_links, links);
+            for code in nodes.iter().map(|(code, _)| Code(code.clone())) {
+                assert_eq!(parallel.best_moves(&code), serial.best_moves(&code));
+            }
+        }
+
+        #[test]
+        fn middle_of_game() {
+            let board = Board3::decode(&Code("ro3o2f_yf_o3h0tr".to_string())).unwrap();
+
+            assert_same_as_serial(&[board]);
+        }
+
+        #[test]
+        fn reuse_decided_boards() {
+            let boards: Vec<_> = ["ro3o1f_yf_o2h0ty", "ro3o2f_yf_o3h0tr"].iter()
+                .map(|code| Board3::decode(&Code(code.to_string())).unwrap())
+                .collect();
+
+            assert_same_as_serial(&boards);
+        }
+
+        #[test]
+        fn colour_symmetric_rules() {
+            let rules = RuleSet { yellow_speeds_outward: [2, 1, 2], yellow_speeds_homeward: [1, 2, 1], ..RuleSet::default() };
+            let board = Board3::decode_with_rules(&Code("ro3o2f_yf_o3h0tr".to_string()), rules).unwrap();
+
+            assert_same_as_serial(&[board]);
+        }
+
+        #[test]
+        fn unknown_distance_of_decided_board() {
+            let board = Board3::decode(&Code("ro3o2f_yf_o3h0tr".to_string())).unwrap();
+            let next_board = board.apply_move(&board.legal_moves()[0]).unwrap();
+            // decided in a previous analysis, e.g. with the distance capped in a tablebase
+            let mut graph = GameGraph::new();
+            graph.add_node(next_board.to_key(), GameResult::RedWins, None);
+            let serial = Analyzer::from_graph(graph, Some(board));
+            let parallel = ParallelAnalyzer::new();
+            parallel.map.insert(next_board.to_key(), ParallelNode { cell: AtomicU64::new(pack_cell(GameResult::RedWins, None)), next_boards: Vec::new() });
+
+            assert_eq!(parallel.analyze(&board), serial.analyze(&board));
+            assert_eq!(parallel.get_distance(&board.encode()), None);
+            for (code, _) in serial.emit_nodes_and_links().0 {
+                let code = Code(code);
+                assert_eq!(parallel.get_distance(&code), serial.get_distance(&code));
+            }
+        }
+
+        #[test]
+        fn nearer_distance_than_capped() {
+            let board = Board3::decode(&Code("ro3o2f_yf_o3h0tr".to_string())).unwrap();
+            let next_boards: Vec<_> = board.legal_moves().iter().map(|m| board.apply_move(m).unwrap()).collect();
+            // decided in previous analyses, the first too far to store
+            let mut graph = GameGraph::new();
+            let parallel = ParallelAnalyzer::new();
+            for (next_board, distance) in next_boards.iter().zip([None, Some(0)].iter()) {
+                graph.add_node(next_board.to_key(), GameResult::RedWins, *distance);
+                parallel.map.insert(next_board.to_key(), ParallelNode { cell: AtomicU64::new(pack_cell(GameResult::RedWins, *distance)), next_boards: Vec::new() });
+            }
+            let serial = Analyzer::from_graph(graph, Some(board));
+
+            assert_eq!(parallel.analyze(&board), GameResult::RedWins);
+            assert_eq!(serial.analyze(&board), GameResult::RedWins);
+            assert_eq!(parallel.get_distance(&board.encode()), Some(1));
+            assert_eq!(serial.get_distance(&board.encode()), Some(1));
+        }
+
+        #[test]
+        #[ignore] // full 3x3, run with `cargo test --release same_as_serial -- --ignored`
+        fn initial_board() {
+            assert_same_as_serial(&[Board3::new(Turn::Red), Board3::new(Turn::Yellow)]);
+
+            let parallel = ParallelAnalyzer::new();
+            assert_eq!(parallel.analyze(&Board3::new(Turn::Red)), GameResult::YellowWins);
+        }
+    }
+}
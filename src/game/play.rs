@@ -0,0 +1,227 @@
+use crate::game::analysis::{Analyzer, BestMove};
+use crate::game::commons::{Board, GameResult, Turn};
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "<piece index> to move, hint, undo, quit";
+
+/// A game of a human against the solver, with the boards so far to undo.
+pub struct Game<'a, B: Board> {
+    analyzer: &'a Analyzer<B>,
+    human: Turn,
+    // from the first board, the last is the current board
+    history: Vec<B>,
+}
+
+impl<'a, B> Game<'a, B> where B: Board {
+    pub fn new(analyzer: &'a Analyzer<B>, board: B, human: Turn) -> Game<'a, B> {
+        Game { analyzer, human, history: vec![board] }
+    }
+
+    pub fn get_board(self: &Self) -> &B {
+        self.history.last().unwrap() // never empty
+    }
+
+    pub fn get_turn(self: &Self) -> Turn {
+        self.get_board().to_key().get_turn::<B>()
+    }
+
+    pub fn is_over(self: &Self) -> bool {
+        self.get_board().get_result() != GameResult::Unknown
+    }
+
+    /// The result when both play the best from the current board, and the distance to it, if analyzed.
+    pub fn get_theoretical_result(self: &Self) -> Option<(GameResult, Option<u32>)> {
+        let code = self.get_board().encode();
+        self.analyzer.get_result(&code).map(|result| (result, self.analyzer.get_distance(&code)))
+    }
+
+    /// Moves the piece of the human. False if it's not a legal move in the turn of the human.
+    pub fn play(self: &mut Self, piece_index: usize) -> bool {
+        if self.is_over() || self.get_turn() != self.human {
+            return false;
+        }
+
+        self.apply(piece_index)
+    }
+
+    /// Moves the best piece for the solver, or any piece if the board is not analyzed. None if the solver can't move.
+    pub fn reply(self: &mut Self) -> Option<usize> {
+        if self.is_over() || self.get_turn() == self.human {
+            return None;
+        }
+
+        let piece_index = self.analyzer.best_move(&self.get_board().encode())
+            .or_else(|| self.get_board().legal_moves().first().map(|m| m.piece_index))?;
+        self.apply(piece_index);
+
+        return Some(piece_index);
+    }
+
+    /// The best moves for the human in the current board.
+    pub fn hint(self: &Self) -> Vec<BestMove> {
+        self.analyzer.best_moves(&self.get_board().encode())
+    }
+
+    /// Goes back to the previous turn of the human, taking back the reply of the solver too. False if there is none.
+    pub fn undo(self: &mut Self) -> bool {
+        let last = self.history.len() - 1;
+        match self.history[..last].iter().rposition(|b| b.to_key().get_turn::<B>() == self.human) {
+            Some(index) => {
+                self.history.truncate(index + 1);
+                true
+            }
+            None => false
+        }
+    }
+
+    fn apply(self: &mut Self, piece_index: usize) -> bool {
+        let next_board = match self.get_board().legal_moves().iter().find(|m| m.piece_index == piece_index) {
+            Some(the_move) => self.get_board().apply_move(the_move).unwrap(), // legal move
+            None => return false
+        };
+        self.history.push(next_board);
+
+        return true;
+    }
+}
+
+/// Plays the game by the commands in the input until the end of the game or the input, writing the boards to the output.
+pub fn run_repl<B: Board>(game: &mut Game<B>, input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    writeln!(output, "{}", HELP)?;
+    write_board(game, output)?;
+    let mut lines = input.lines();
+
+    loop {
+        if game.is_over() {
+            writeln!(output, "game over: {}", game.get_board().get_result())?;
+            return Ok(());
+        }
+
+        if let Some(piece_index) = game.reply() {
+            writeln!(output, "solver moves {}", piece_index)?;
+            write_board(game, output)?;
+            continue;
+        }
+
+        write!(output, "> ")?;
+        output.flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(())
+        };
+
+        match line.trim() {
+            "quit" => return Ok(()),
+            "undo" => if game.undo() {
+                write_board(game, output)?;
+            } else {
+                writeln!(output, "nothing to undo")?;
+            },
+            "hint" => for best_move in game.hint() {
+                writeln!(output, "hint: {} ({}, distance: {:?})", best_move.piece_index, best_move.game_result, best_move.distance)?;
+            },
+            command => match command.parse() {
+                Ok(piece_index) if game.play(piece_index) => write_board(game, output)?,
+                Ok(piece_index) => writeln!(output, "no move of {}", piece_index)?,
+                Err(_) => writeln!(output, "unknown command: {} ({})", command, HELP)?,
+            }
+        }
+    }
+}
+
+fn write_board<B: Board>(game: &Game<B>, output: &mut impl Write) -> io::Result<()> {
+    writeln!(output, "{}", game.get_board().draw_ascii_art())?;
+    match game.get_theoretical_result() {
+        Some((result, distance)) => writeln!(output, "theoretical result: {} (distance: {:?})", result, distance),
+        None => writeln!(output, "theoretical result: not analyzed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod game {
+        use super::super::{run_repl, Game};
+        use crate::game::analysis::Analyzer;
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Board, Code, GameResult, Turn};
+
+        // yellow wins in 8 plies with red to move
+        const CODE: &str = "ro3o2f_yf_o3h0tr";
+
+        fn analyze() -> (Analyzer<Board3>, Board3) {
+            let board = Board3::decode(&Code(CODE.to_string())).unwrap();
+            let analyzer = Analyzer::new();
+            analyzer.analyze(&board);
+
+            (analyzer, board)
+        }
+
+        #[test]
+        fn play_and_undo() {
+            let (analyzer, board) = analyze();
+            let mut game = Game::new(&analyzer, board, Turn::Red);
+
+            assert_eq!(game.get_theoretical_result(), Some((GameResult::YellowWins, Some(8))));
+            assert!(!game.undo());
+            assert_eq!(game.reply(), None);
+            assert!(!game.play(9));
+
+            let piece_index = game.hint()[0].piece_index;
+            assert!(game.play(piece_index));
+            assert!(!game.play(piece_index));
+            assert_eq!(game.get_theoretical_result(), Some((GameResult::YellowWins, Some(7))));
+            assert!(game.reply().is_some());
+            assert_eq!(game.get_theoretical_result(), Some((GameResult::YellowWins, Some(6))));
+
+            assert!(game.undo());
+            assert_eq!(game.get_board().encode(), board.encode());
+        }
+
+        #[test]
+        fn solver_wins_against_hints() {
+            let (analyzer, board) = analyze();
+            let mut game = Game::new(&analyzer, board, Turn::Red);
+
+            while !game.is_over() {
+                let piece_index = game.hint()[0].piece_index;
+                assert!(game.play(piece_index));
+                game.reply();
+            }
+
+            assert_eq!(game.get_board().get_result(), GameResult::YellowWins);
+        }
+
+        #[test]
+        fn repl() {
+            let (analyzer, board) = analyze();
+            let mut game = Game::new(&analyzer, board, Turn::Red);
+            let mut output = Vec::new();
+            let input = format!("hint\n9\nfoo\n{}\nundo\nquit\n0\n", game.hint()[0].piece_index);
+
+            run_repl(&mut game, input.as_bytes(), &mut output).unwrap();
+
+            let output = String::from_utf8(output).unwrap();
+            assert!(output.contains("theoretical result: YellowWins (distance: Some(8))"));
+            assert!(output.contains("hint: "));
+            assert!(output.contains("no move of 9"));
+            assert!(output.contains("unknown command: foo"));
+            assert!(output.contains("solver moves "));
+            assert!(output.contains("theoretical result: YellowWins (distance: Some(6))"));
+            assert_eq!(output.matches("next-> Red").count(), 3);
+        }
+
+        #[test]
+        fn repl_until_the_end() {
+            let (analyzer, board) = analyze();
+            let mut game = Game::new(&analyzer, board, Turn::Red);
+            let mut output = Vec::new();
+
+            // any moves of red lose against the solver
+            run_repl(&mut game, "0\n1\n2\n".repeat(10).as_bytes(), &mut output).unwrap();
+
+            let output = String::from_utf8(output).unwrap();
+            assert!(output.starts_with("<piece index>"));
+            assert!(output.ends_with("game over: YellowWins\n"));
+        }
+    }
+}
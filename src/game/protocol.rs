@@ -0,0 +1,196 @@
+use crate::game::analysis::Analyzer;
+use crate::game::commons::{Board, Code, GameResult};
+use std::io::{self, BufRead, Write};
+
+// A line-based protocol like UCI, one command per line and the responses in lines:
+//   isready                          -> readyok
+//   position startpos [moves <piece index> ...]
+//   position <code> [moves <piece index> ...]
+//                                    -> (nothing, or error <message> keeping the last position)
+//   board                            -> board <code>
+//   result                           -> result <red|yellow|undeterminable> [distance <plies>]
+//   bestmove                         -> bestmove <piece index|none>
+//   go                               -> info result ... then bestmove ...
+//   quit
+// Anything else is answered by "error unknown command <line>". The positions not analyzed yet are analyzed first.
+
+/// The engine of the protocol over the analyzer, e.g. restored from a tablebase.
+pub struct Engine<'a, B: Board> {
+    analyzer: &'a Analyzer<B>,
+    // for "startpos"
+    initial_board: B,
+    board: B,
+}
+
+impl<'a, B> Engine<'a, B> where B: Board {
+    pub fn new(analyzer: &'a Analyzer<B>, initial_board: B) -> Engine<'a, B> {
+        Engine { analyzer, board: initial_board.clone(), initial_board }
+    }
+
+    /// Responses to the command line. None to quit.
+    pub fn execute(self: &mut Self, line: &str) -> Option<Vec<String>> {
+        let words: Vec<_> = line.split_whitespace().collect();
+
+        let responses = match words.as_slice() {
+            [] => Vec::new(),
+            ["quit"] => return None,
+            ["isready"] => vec!["readyok".to_string()],
+            ["position", position, moves @ ..] => match self.set_position(position, moves) {
+                Ok(()) => Vec::new(),
+                Err(message) => vec![format!("error {}", message)],
+            },
+            ["board"] => vec![format!("board {}", self.board.encode().0)],
+            ["result"] => vec![self.get_result()],
+            ["bestmove"] => vec![self.get_best_move()],
+            ["go"] => vec![format!("info {}", self.get_result()), self.get_best_move()],
+            _ => vec![format!("error unknown command {}", line.trim())],
+        };
+
+        return Some(responses);
+    }
+
+    fn set_position(self: &mut Self, position: &str, moves: &[&str]) -> Result<(), String> {
+        let mut board = match position {
+            "startpos" => self.initial_board.clone(),
+            code => B::decode(&Code(code.to_string())).map_err(|e| format!("invalid position {}: {}", code, e))?,
+        };
+
+        let piece_indices = match moves {
+            [] => &[][..],
+            ["moves", piece_indices @ ..] => piece_indices,
+            _ => return Err(format!("expected moves: {}", moves.join(" "))),
+        };
+        for piece_index in piece_indices {
+            let the_move = piece_index.parse().ok()
+                .and_then(|i: usize| board.legal_moves().into_iter().find(|m| m.piece_index == i))
+                .ok_or_else(|| format!("illegal move {}", piece_index))?;
+            board = board.apply_move(&the_move).unwrap(); // legal move
+        }
+
+        self.board = board;
+
+        Ok(())
+    }
+
+    // the positions out of the analysis, e.g. unreachable from the initial boards, are analyzed here
+    fn analyze_if_needed(self: &Self) -> Code {
+        let code = self.board.encode();
+        if self.board.get_result() == GameResult::Unknown && self.analyzer.get_result(&code).is_none() {
+            self.analyzer.analyze(&self.board);
+        }
+
+        return code;
+    }
+
+    fn get_result(self: &Self) -> String {
+        let code = self.analyze_if_needed();
+        let (result, distance) = match self.board.get_result() {
+            GameResult::Unknown => (self.analyzer.get_result(&code).unwrap_or(GameResult::Unknown), self.analyzer.get_distance(&code)),
+            result => (result, Some(0)),
+        };
+
+        let result = match result {
+            GameResult::RedWins => "red",
+            GameResult::YellowWins => "yellow",
+            GameResult::Undeterminable => "undeterminable",
+            GameResult::Unknown | GameResult::Invalid => "unknown",
+        };
+        match distance {
+            Some(distance) => format!("result {} distance {}", result, distance),
+            None => format!("result {}", result),
+        }
+    }
+
+    fn get_best_move(self: &Self) -> String {
+        let code = self.analyze_if_needed();
+        match self.analyzer.best_move(&code) {
+            Some(piece_index) => format!("bestmove {}", piece_index),
+            None => "bestmove none".to_string(),
+        }
+    }
+}
+
+/// Answers the commands in the input until "quit" or the end of the input, flushing the output after each command.
+pub fn run_engine<B: Board>(engine: &mut Engine<B>, input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    for line in input.lines() {
+        let responses = match engine.execute(&line?) {
+            Some(responses) => responses,
+            None => break
+        };
+        for response in responses {
+            writeln!(output, "{}", response)?;
+        }
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    mod engine {
+        use super::super::{run_engine, Engine};
+        use crate::game::analysis::Analyzer;
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Code, Turn};
+
+        // yellow wins in 8 plies with red to move
+        const CODE: &str = "ro3o2f_yf_o3h0tr";
+
+        fn execute(engine: &mut Engine<Board3>, line: &str) -> Vec<String> {
+            engine.execute(line).unwrap()
+        }
+
+        #[test]
+        fn commands() {
+            let analyzer = Analyzer::new();
+            let mut engine = Engine::new(&analyzer, Board3::new(Turn::Red));
+
+            assert_eq!(execute(&mut engine, "isready"), vec!["readyok"]);
+            assert_eq!(execute(&mut engine, "board"), vec!["board ro0o0o0yo0o0o0tr"]);
+            assert_eq!(execute(&mut engine, &format!("position {}", CODE)), Vec::<String>::new());
+            assert_eq!(execute(&mut engine, "result"), vec!["result yellow distance 8"]);
+
+            let best_move = analyzer.best_move(&Code(CODE.to_string())).unwrap();
+            assert_eq!(execute(&mut engine, "bestmove"), vec![format!("bestmove {}", best_move)]);
+            assert_eq!(execute(&mut engine, "go"), vec!["info result yellow distance 8".to_string(), format!("bestmove {}", best_move)]);
+
+            assert_eq!(execute(&mut engine, &format!("position {} moves {}", CODE, best_move)), Vec::<String>::new());
+            assert_eq!(execute(&mut engine, "result"), vec!["result yellow distance 7"]);
+            assert!(engine.execute("quit").is_none());
+        }
+
+        #[test]
+        fn errors() {
+            let analyzer = Analyzer::new();
+            let mut engine = Engine::new(&analyzer, Board3::new(Turn::Red));
+
+            assert_eq!(execute(&mut engine, "position startpos moves 0 9"), vec!["error illegal move 9"]);
+            assert_eq!(execute(&mut engine, "position startpos 0"), vec!["error expected moves: 0"]);
+            assert!(execute(&mut engine, "position invalid")[0].starts_with("error invalid position invalid"));
+            assert_eq!(execute(&mut engine, "board"), vec!["board ro0o0o0yo0o0o0tr"]);
+            assert_eq!(execute(&mut engine, "fly"), vec!["error unknown command fly"]);
+            assert_eq!(execute(&mut engine, ""), Vec::<String>::new());
+        }
+
+        #[test]
+        fn finished_board() {
+            let analyzer = Analyzer::new();
+            let mut engine = Engine::new(&analyzer, Board3::new(Turn::Red));
+
+            assert_eq!(execute(&mut engine, "position rf_f_o0yo0o0o0ty"), Vec::<String>::new());
+            assert_eq!(execute(&mut engine, "go"), vec!["info result red distance 0", "bestmove none"]);
+        }
+
+        #[test]
+        fn script() {
+            let analyzer = Analyzer::new();
+            let mut engine = Engine::new(&analyzer, Board3::new(Turn::Red));
+            let mut output = Vec::new();
+
+            run_engine(&mut engine, format!("isready\nposition {}\nresult\nquit\nisready\n", CODE).as_bytes(), &mut output).unwrap();
+
+            assert_eq!(String::from_utf8(output).unwrap(), "readyok\nresult yellow distance 8\n");
+        }
+    }
+}
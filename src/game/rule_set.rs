@@ -0,0 +1,30 @@
+use crate::game::commons::Turn;
+
+/// Rules of a game on the board with N pieces for each player.
+/// Index of each speed table is the same as the piece index.
+/// The official rules are provided as `Default` of the supported board sizes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RuleSet<const N: usize> {
+    pub red_speeds_outward: [u8; N],
+    pub yellow_speeds_outward: [u8; N],
+    pub red_speeds_homeward: [u8; N],
+    pub yellow_speeds_homeward: [u8; N],
+    pub pieces_to_win: usize,
+    pub first_move: Turn,
+}
+
+impl<const N: usize> RuleSet<N> {
+    pub fn get_speed_outward(self: &Self, turn: Turn, piece_index: usize) -> u8 {
+        if turn == Turn::Red { self.red_speeds_outward[piece_index] } else { self.yellow_speeds_outward[piece_index] }
+    }
+
+    pub fn get_speed_homeward(self: &Self, turn: Turn, piece_index: usize) -> u8 {
+        if turn == Turn::Red { self.red_speeds_homeward[piece_index] } else { self.yellow_speeds_homeward[piece_index] }
+    }
+
+    /// Whether both colours have the same speeds, i.e. swapping the colours doesn't change the game.
+    /// The official rules are not, as red and yellow pieces of the same index have different speeds.
+    pub fn is_colour_symmetric(self: &Self) -> bool {
+        self.red_speeds_outward == self.yellow_speeds_outward && self.red_speeds_homeward == self.yellow_speeds_homeward
+    }
+}
@@ -0,0 +1,182 @@
+use crate::game::analysis::Analyzer;
+use crate::game::commons::{Board, Code, GameResult, Turn};
+use serde_json::{json, Value};
+use std::io;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// GET /position/<code> answers
+//   {"code", "turn": "red"|"yellow", "result", "distance", "best_moves": [move], "children": [move], "ascii_art"}
+//   move: {"piece", "move", "board", "result", "distance"}
+// where the results are "red", "yellow", "undeterminable" or "unknown" (not analyzed), and the distances are plies or null.
+// All distances are counted from the board of the code, i.e. the distance of a move is the one of its next board + 1,
+// as `BestMove` and the `query` command.
+// The errors are {"error": message} with the status.
+const POSITION_PATH: &str = "/position/";
+
+/// Answers the requests to the server one by one, until the server is closed.
+/// A failure of a response, e.g. the client disconnected, is logged and the others are answered.
+pub fn serve<B: Board>(analyzer: &Analyzer<B>, server: &Server) {
+    for request in server.incoming_requests() {
+        if let Err(e) = handle(analyzer, request) {
+            eprintln!("failed to respond: {}", e);
+        }
+    }
+}
+
+/// Answers a request by JSON.
+pub fn handle<B: Board>(analyzer: &Analyzer<B>, request: Request) -> io::Result<()> {
+    let (status, body) = if *request.method() != Method::Get {
+        (405, json!({ "error": "only GET" }))
+    } else {
+        respond(analyzer, request.url())
+    };
+
+    let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap(); // valid header
+    request.respond(Response::from_string(body.to_string()).with_status_code(status).with_header(header))
+}
+
+/// Status and JSON body for the path of the request.
+pub fn respond<B: Board>(analyzer: &Analyzer<B>, url: &str) -> (u16, Value) {
+    let code = match url.strip_prefix(POSITION_PATH) {
+        Some(code) if !code.is_empty() && !code.contains('/') => Code(code.to_string()),
+        _ => return (404, json!({ "error": format!("no such path: {}, use {}<code>", url, POSITION_PATH) }))
+    };
+    let board = match B::decode(&code) {
+        Ok(board) => board,
+        Err(e) => return (400, json!({ "error": format!("invalid code {}: {}", code.0, e) }))
+    };
+    // the URL may spell the board other than its canonical code, e.g. o03 for o3
+    let code = board.encode();
+
+    let children: Vec<_> = board.legal_moves().iter()
+        .map(|m| {
+            let next_board = board.apply_move(m).unwrap(); // legal move
+            let (result, distance) = get_result(analyzer, &next_board);
+            let distance = distance.map(|d| d + 1);
+            json!({ "piece": m.piece_index, "move": m.to_string(), "board": next_board.encode(), "result": result, "distance": distance })
+        })
+        .collect();
+    let best_moves: Vec<_> = analyzer.best_moves(&code).iter()
+        .filter_map(|m| children.iter().find(|c| c["piece"] == m.piece_index).cloned())
+        .collect();
+    let (result, distance) = get_result(analyzer, &board);
+
+    (200, json!({
+        "code": code,
+        "turn": if code.get_turn::<B>() == Turn::Red { "red" } else { "yellow" },
+        "result": result,
+        "distance": distance,
+        "best_moves": best_moves,
+        "children": children,
+        "ascii_art": board.draw_ascii_art(),
+    }))
+}
+
+// the finished boards are decided without the analysis
+fn get_result<B: Board>(analyzer: &Analyzer<B>, board: &B) -> (GameResult, Option<u32>) {
+    if board.get_result() != GameResult::Unknown {
+        return (board.get_result(), Some(0));
+    }
+
+    let code = board.encode();
+    (analyzer.get_result(&code).unwrap_or(GameResult::Unknown), analyzer.get_distance(&code))
+}
+
+#[cfg(test)]
+mod tests {
+    mod server {
+        use super::super::{handle, respond};
+        use crate::game::analysis::Analyzer;
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Board, Code};
+        use serde_json::Value;
+        use std::io::{Read, Write};
+        use std::net::TcpStream;
+
+        // yellow wins in 8 plies with red to move
+        const CODE: &str = "ro3o2f_yf_o3h0tr";
+
+        fn analyze() -> Analyzer<Board3> {
+            let analyzer = Analyzer::new();
+            analyzer.analyze(&Board3::decode(&Code(CODE.to_string())).unwrap());
+            analyzer
+        }
+
+        #[test]
+        fn position() {
+            let analyzer = analyze();
+
+            let (status, body) = respond(&analyzer, &format!("/position/{}", CODE));
+
+            assert_eq!(status, 200);
+            assert_eq!(body["code"], CODE);
+            assert_eq!(body["turn"], "red");
+            assert_eq!(body["result"], "yellow");
+            assert_eq!(body["distance"], 8);
+            assert_eq!(body["children"].as_array().unwrap().len(), 2);
+            let best_moves = body["best_moves"].as_array().unwrap();
+            assert_eq!(best_moves.len(), analyzer.best_moves(&Code(CODE.to_string())).len());
+            assert!(best_moves.iter().all(|m| m["result"] == "yellow" && m["distance"] == 8));
+            assert!(body["children"].as_array().unwrap().iter().all(|c| c["distance"].as_u64() <= Some(8)));
+            assert!(body["ascii_art"].as_str().unwrap().contains("next-> Red"));
+        }
+
+        #[test]
+        fn non_canonical_code() {
+            let analyzer = analyze();
+
+            let (status, body) = respond(&analyzer, "/position/ro03o2f_yf_o3h0tr");
+
+            assert_eq!(status, 200);
+            assert_eq!(body, respond(&analyzer, &format!("/position/{}", CODE)).1);
+            assert!(!body["best_moves"].as_array().unwrap().is_empty());
+        }
+
+        #[test]
+        fn errors() {
+            let analyzer = analyze();
+
+            assert_eq!(respond(&analyzer, "/position/invalid").0, 400);
+            assert_eq!(respond(&analyzer, "/position/").0, 404);
+            assert_eq!(respond(&analyzer, "/board").0, 404);
+
+            // not reachable from the analyzed board
+            let (status, body) = respond(&analyzer, "/position/ro0o0o0yo0o0o0tr");
+            assert_eq!(status, 200);
+            assert_eq!(body["result"], "unknown");
+            assert_eq!(body["distance"], Value::Null);
+            assert!(body["best_moves"].as_array().unwrap().is_empty());
+        }
+
+        // the raw HTTP response of the request to the server
+        fn request(address: std::net::SocketAddr, request: String) -> std::thread::JoinHandle<String> {
+            std::thread::spawn(move || {
+                let mut stream = TcpStream::connect(address).unwrap();
+                stream.write_all(request.as_bytes()).unwrap();
+                let mut response = String::new();
+                stream.read_to_string(&mut response).unwrap();
+                response
+            })
+        }
+
+        #[test]
+        fn on_localhost() {
+            let analyzer = analyze();
+            let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+            let address = server.server_addr().to_ip().unwrap();
+
+            let client = request(address, format!("GET /position/{} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", CODE));
+            handle(&analyzer, server.recv().unwrap()).unwrap();
+            let response = client.join().unwrap();
+
+            assert!(response.starts_with("HTTP/1.1 200"));
+            assert!(response.contains("Content-Type: application/json"));
+            let body: Value = serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+            assert_eq!(body["result"], "yellow");
+
+            let client = request(address, "POST /position/x HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 0\r\n\r\n".to_string());
+            handle(&analyzer, server.recv().unwrap()).unwrap();
+            assert!(client.join().unwrap().starts_with("HTTP/1.1 405"));
+        }
+    }
+}
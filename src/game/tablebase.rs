@@ -0,0 +1,536 @@
+use crate::game::analysis::{Analyzer, BestMove, RankedResults};
+use crate::game::board_n::BoardN;
+use crate::game::commons::{Board, Code, GameResult, Turn, get_n_ranks};
+use crate::game::dense_table::{encode_cell, decode_cell};
+use crate::game::rule_set::RuleSet;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashSet};
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// header: magic, encoding version (u16), board size (u16), rules, number of ranks (u64),
+//         number of the stored blocks (u64), checksum of the header before it and the index (u32)
+//         rules: red outward, yellow outward, red homeward, yellow homeward speeds (N bytes each),
+//                pieces to win (u8), first move (0 = red, 1 = yellow)
+// blocks: `RANKS_PER_BLOCK` records of the consecutive ranks from the block number * `RANKS_PER_BLOCK`,
+//         only the blocks which have any analyzed boards, in order of the block number
+//         record: (result and distance by `encode_cell`, index of the best piece to move or NO_MOVE)
+// index: (block number (u64), checksum of the block (u32)) of each stored block, after the blocks
+// All numbers are little endian, and the checksums are CRC-32. The boards of the blocks not stored are unknown.
+const MAGIC: &[u8; 8] = b"SQDRTB\r\n";
+pub const ENCODING_VERSION: u16 = 3;
+const RECORD_SIZE: u64 = 2;
+const NO_MOVE: u8 = u8::MAX;
+const RANKS_PER_BLOCK: u64 = 4096;
+const BLOCK_SIZE: u64 = RANKS_PER_BLOCK * RECORD_SIZE;
+const INDEX_ENTRY_SIZE: u64 = 8 + 4;
+const UNKNOWN_RECORD: [u8; RECORD_SIZE as usize] = [0, NO_MOVE];
+
+/// An entry of the tablebase.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Probe {
+    pub game_result: GameResult,
+    // plies to the end of the game, None if undeterminable or too far to store
+    pub distance: Option<u32>,
+    pub best_move: Option<usize>,
+}
+
+const UNKNOWN_PROBE: Probe = Probe { game_result: GameResult::Unknown, distance: None, best_move: None };
+
+#[derive(Debug)]
+pub enum TablebaseError {
+    Io(std::io::Error),
+    Format(String),
+    Version(u16),
+    BoardSize(usize),
+    Rules,
+    Checksum,
+}
+
+impl std::fmt::Display for TablebaseError {
+    fn fmt(self: &Self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TablebaseError::Io(e) => write!(f, "{}", e),
+            TablebaseError::Format(s) => write!(f, "invalid tablebase: {}", s),
+            TablebaseError::Version(v) => write!(f, "unsupported encoding version: {} (expected {})", v, ENCODING_VERSION),
+            TablebaseError::BoardSize(n) => write!(f, "tablebase of another board size: {}", n),
+            TablebaseError::Rules => write!(f, "tablebase of other rules"),
+            TablebaseError::Checksum => write!(f, "checksum mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for TablebaseError {}
+
+impl From<std::io::Error> for TablebaseError {
+    fn from(e: std::io::Error) -> Self {
+        TablebaseError::Io(e)
+    }
+}
+
+/// Results and best moves of the analyzed boards of N pieces in a file, which are read block by block for each probe.
+pub struct Tablebase<const N: usize> {
+    file: RefCell<File>,
+    rules: RuleSet<N>,
+    // (block number, checksum) of the stored blocks in order, whose position is the one in the file
+    index: Vec<(u64, u32)>,
+    // whether each stored block is verified so far by the checksum
+    verified: RefCell<Vec<bool>>,
+}
+
+impl<const N: usize> Tablebase<N> where RuleSet<N>: Default {
+    const HEADER_SIZE: u64 = 8 + 2 + 2 + 4 * N as u64 + 2 + 8 + 8 + 4;
+
+    /// Writes the results of the analyzer in the blocks of the analyzed boards. The other boards are unknown.
+    pub fn create(path: &Path, analyzer: &Analyzer<BoardN<N>>) -> Result<(), TablebaseError> {
+        let rules = analyzer.get_sample_board().map_or_else(RuleSet::default, |b| *b.get_rules());
+        let mut file = BufWriter::new(File::create(path)?);
+
+        let mut header = Vec::with_capacity(Self::HEADER_SIZE as usize);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&ENCODING_VERSION.to_le_bytes());
+        header.extend_from_slice(&(N as u16).to_le_bytes());
+        header.extend_from_slice(&Self::encode_rules(&rules));
+        header.extend_from_slice(&get_n_ranks(N).to_le_bytes());
+        file.write_all(&vec![0; Self::HEADER_SIZE as usize])?; // written after the index with the number of the blocks and the checksum
+
+        // the colour-swapped boards are not stored in the analyzer but in the tablebase
+        let mut block_numbers = BTreeSet::new();
+        for rank in analyzer.get_stored_ranks() {
+            block_numbers.insert(rank / RANKS_PER_BLOCK);
+            let swapped = BoardN::from_rank_with_rules(rank, rules).and_then(|b| b.get_colour_swapped());
+            if let Some(swapped_rank) = swapped.and_then(|b| b.to_rank()) {
+                block_numbers.insert(swapped_rank / RANKS_PER_BLOCK);
+            }
+        }
+
+        let mut index = Vec::new();
+        let mut block = Vec::with_capacity(BLOCK_SIZE as usize);
+        for block_number in block_numbers {
+            block.clear();
+            for rank in block_number * RANKS_PER_BLOCK..(block_number + 1) * RANKS_PER_BLOCK {
+                block.extend_from_slice(&Self::get_record(analyzer, rank, rules));
+            }
+            if block.chunks(RECORD_SIZE as usize).all(|record| record == UNKNOWN_RECORD) {
+                continue;
+            }
+
+            file.write_all(&block)?;
+            index.push((block_number, crc32fast::hash(&block)));
+        }
+
+        header.extend_from_slice(&(index.len() as u64).to_le_bytes());
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&header);
+        for (block_number, checksum) in index.iter() {
+            let entry = [block_number.to_le_bytes().to_vec(), checksum.to_le_bytes().to_vec()].concat();
+            hasher.update(&entry);
+            file.write_all(&entry)?;
+        }
+        header.extend_from_slice(&hasher.finalize().to_le_bytes());
+
+        let mut file = file.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&header)?;
+
+        Ok(())
+    }
+
+    // unknown out of the ranks, e.g. in the last block
+    fn get_record(analyzer: &Analyzer<BoardN<N>>, rank: u64, rules: RuleSet<N>) -> [u8; RECORD_SIZE as usize] {
+        let key = match BoardN::from_rank_with_rules(rank, rules) {
+            Some(board) => board.to_key(),
+            None => return UNKNOWN_RECORD
+        };
+
+        match analyzer.get_solved(&key) {
+            Some((result, distance)) => {
+                let best_move = analyzer.best_moves_of_key(&key).first().map_or(NO_MOVE, |m| m.0 as u8);
+                [encode_cell(result, distance), best_move]
+            }
+            None => UNKNOWN_RECORD
+        }
+    }
+
+    /// Reads the header and the index, which are verified together by the checksum before the rules are read.
+    /// The blocks are verified when probed, or all at once by `verify`.
+    pub fn open(path: &Path) -> Result<Tablebase<N>, TablebaseError> {
+        let mut file = File::open(path)?;
+        let mut header = vec![0; Self::HEADER_SIZE as usize];
+        file.read_exact(&mut header[..12]).map_err(|_| TablebaseError::Format("too short".to_string()))?;
+
+        if &header[..8] != MAGIC {
+            return Err(TablebaseError::Format("not a tablebase".to_string()));
+        }
+        let version = u16::from_le_bytes([header[8], header[9]]);
+        if version != ENCODING_VERSION {
+            return Err(TablebaseError::Version(version));
+        }
+        let board_size = u16::from_le_bytes([header[10], header[11]]) as usize;
+        if board_size != N {
+            return Err(TablebaseError::BoardSize(board_size));
+        }
+
+        file.read_exact(&mut header[12..]).map_err(|_| TablebaseError::Format("too short".to_string()))?;
+        let numbers = &header[12 + 4 * N + 2..];
+        let n_blocks = u64::from_le_bytes(numbers[8..16].try_into().unwrap());
+        let checksum = u32::from_le_bytes(numbers[16..].try_into().unwrap());
+
+        let index_start = n_blocks.checked_mul(BLOCK_SIZE).and_then(|size| size.checked_add(Self::HEADER_SIZE));
+        let file_size = index_start.and_then(|start| n_blocks.checked_mul(INDEX_ENTRY_SIZE).and_then(|size| start.checked_add(size)));
+        if file_size != Some(file.metadata()?.len()) {
+            return Err(TablebaseError::Format("size of the file".to_string()));
+        }
+
+        let mut index_bytes = vec![0; (n_blocks * INDEX_ENTRY_SIZE) as usize];
+        file.seek(SeekFrom::Start(index_start.unwrap()))?; // checked by the size
+        file.read_exact(&mut index_bytes)?;
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&header[..Self::HEADER_SIZE as usize - 4]);
+        hasher.update(&index_bytes);
+        if hasher.finalize() != checksum {
+            return Err(TablebaseError::Checksum);
+        }
+
+        let rules = Self::decode_rules(&header[12..12 + 4 * N + 2])?;
+        if u64::from_le_bytes(numbers[..8].try_into().unwrap()) != get_n_ranks(N) {
+            return Err(TablebaseError::Format("number of ranks".to_string()));
+        }
+
+        let index: Vec<_> = index_bytes.chunks(INDEX_ENTRY_SIZE as usize)
+            .map(|entry| (u64::from_le_bytes(entry[..8].try_into().unwrap()), u32::from_le_bytes(entry[8..].try_into().unwrap())))
+            .collect();
+        if !index.windows(2).all(|w| w[0].0 < w[1].0) || index.last().is_some_and(|(n, _)| *n * RANKS_PER_BLOCK >= get_n_ranks(N)) {
+            return Err(TablebaseError::Format("block numbers".to_string()));
+        }
+
+        let verified = RefCell::new(vec![false; index.len()]);
+        Ok(Tablebase { file: RefCell::new(file), rules, index, verified })
+    }
+
+    pub fn get_rules(self: &Self) -> &RuleSet<N> {
+        &self.rules
+    }
+
+    /// Reads only the block of the board, which must be of the same rules except the first move.
+    /// The block is verified by the checksum at the first probe.
+    pub fn probe(self: &Self, board: &BoardN<N>) -> Result<Probe, TablebaseError> {
+        let rules = RuleSet { first_move: self.rules.first_move, ..*board.get_rules() };
+        if rules != self.rules {
+            return Err(TablebaseError::Rules);
+        }
+
+        // the boards not legal are never reached
+        match board.to_rank() {
+            Some(rank) => self.probe_rank(rank),
+            None => Ok(UNKNOWN_PROBE)
+        }
+    }
+
+    fn probe_rank(self: &Self, rank: u64) -> Result<Probe, TablebaseError> {
+        let position = match self.index.binary_search_by_key(&(rank / RANKS_PER_BLOCK), |(n, _)| *n) {
+            Ok(position) => position,
+            Err(_) => return Ok(UNKNOWN_PROBE)
+        };
+
+        let mut record = [0; RECORD_SIZE as usize];
+        if self.verified.borrow()[position] {
+            let mut file = self.file.borrow_mut();
+            file.seek(SeekFrom::Start(Self::get_block_start(position) + rank % RANKS_PER_BLOCK * RECORD_SIZE))?;
+            file.read_exact(&mut record)?;
+        } else {
+            let block = self.read_block(position)?;
+            let offset = (rank % RANKS_PER_BLOCK * RECORD_SIZE) as usize;
+            record.copy_from_slice(&block[offset..offset + RECORD_SIZE as usize]);
+        }
+
+        Ok(Self::decode_record(&record))
+    }
+
+    /// Verifies all blocks by the checksums.
+    pub fn verify(self: &Self) -> Result<(), TablebaseError> {
+        self.for_each_probe(|_, _| {})
+    }
+
+    /// Best moves of the board by probing the next boards, as `Analyzer::best_moves`.
+    pub fn best_moves(self: &Self, board: &BoardN<N>) -> Result<Vec<BestMove>, TablebaseError> {
+        let turn = board.turn;
+        let mut candidates = Vec::new();
+        for the_move in board.legal_moves() {
+            let next_board = board.apply_move(&the_move).unwrap(); // legal move
+            let probe = self.probe(&next_board)?;
+            if probe.game_result != GameResult::Unknown {
+                let score = Analyzer::<BoardN<N>>::get_score(turn, probe.game_result, probe.distance);
+                candidates.push((BestMove {
+                    piece_index: the_move.piece_index,
+                    next_board: next_board.encode(),
+                    game_result: probe.game_result,
+                    distance: probe.distance.map(|d| d + 1),
+                }, score));
+            }
+        }
+
+        let best_score = candidates.iter().map(|(_, score)| *score).max();
+        Ok(candidates.into_iter().filter(|(_, score)| Some(*score) == best_score).map(|(best_move, _)| best_move).collect())
+    }
+
+    /// The line of boards from the board by the stored best moves, including the board itself.
+    /// Empty if the board is unknown.
+    pub fn principal_variation(self: &Self, board: &BoardN<N>) -> Result<Vec<Code>, TablebaseError> {
+        let mut probe = self.probe(board)?;
+        if probe.game_result == GameResult::Unknown {
+            return Ok(Vec::new());
+        }
+
+        let mut board = *board;
+        let mut line = vec![board.encode()];
+        let mut appeared = HashSet::new();
+        appeared.insert(board.to_key());
+
+        while let Some(next_board) = probe.best_move.and_then(|piece_index| board.move_at(piece_index)) {
+            line.push(next_board.encode());
+            if !appeared.insert(next_board.to_key()) {
+                break;
+            }
+            probe = self.probe(&next_board)?;
+            board = next_board;
+        }
+
+        Ok(line)
+    }
+
+    /// Reads the entries of the stored blocks in order of the rank without keeping them, verifying each block.
+    /// The boards out of the stored blocks are unknown.
+    pub fn for_each_probe<F>(self: &Self, mut f: F) -> Result<(), TablebaseError> where F: FnMut(u64, Probe) {
+        for (position, (block_number, _)) in self.index.iter().enumerate() {
+            let block = self.read_block(position)?;
+            for (i, record) in block.chunks(RECORD_SIZE as usize).enumerate() {
+                let rank = block_number * RANKS_PER_BLOCK + i as u64;
+                if rank < get_n_ranks(N) {
+                    f(rank, Self::decode_record(record.try_into().unwrap())); // RECORD_SIZE bytes
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_block_start(position: usize) -> u64 {
+        Self::HEADER_SIZE + position as u64 * BLOCK_SIZE
+    }
+
+    // the block at the position in the file, verified by the checksum
+    fn read_block(self: &Self, position: usize) -> Result<Vec<u8>, TablebaseError> {
+        let mut block = vec![0; BLOCK_SIZE as usize];
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(Self::get_block_start(position)))?;
+        file.read_exact(&mut block)?;
+
+        if crc32fast::hash(&block) != self.index[position].1 {
+            return Err(TablebaseError::Checksum);
+        }
+        self.verified.borrow_mut()[position] = true;
+
+        Ok(block)
+    }
+
+    fn decode_record(record: &[u8; RECORD_SIZE as usize]) -> Probe {
+        let (game_result, distance) = decode_cell(record[0]);
+        let best_move = if record[1] == NO_MOVE { None } else { Some(record[1] as usize) };
+
+        Probe { game_result, distance, best_move }
+    }
+
+    fn encode_rules(rules: &RuleSet<N>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&rules.red_speeds_outward);
+        bytes.extend_from_slice(&rules.yellow_speeds_outward);
+        bytes.extend_from_slice(&rules.red_speeds_homeward);
+        bytes.extend_from_slice(&rules.yellow_speeds_homeward);
+        bytes.push(rules.pieces_to_win as u8);
+        bytes.push(if rules.first_move == Turn::Red { 0 } else { 1 });
+        bytes
+    }
+
+    fn decode_rules(bytes: &[u8]) -> Result<RuleSet<N>, TablebaseError> {
+        let speeds = |i: usize| -> [u8; N] { bytes[i * N..(i + 1) * N].try_into().unwrap() }; // N bytes
+
+        Ok(RuleSet {
+            red_speeds_outward: speeds(0),
+            yellow_speeds_outward: speeds(1),
+            red_speeds_homeward: speeds(2),
+            yellow_speeds_homeward: speeds(3),
+            pieces_to_win: bytes[4 * N] as usize,
+            first_move: match bytes[4 * N + 1] {
+                0 => Turn::Red,
+                1 => Turn::Yellow,
+                _ => return Err(TablebaseError::Format("first move".to_string()))
+            },
+        })
+    }
+}
+
+// Loaded by `Analyzer::from_tablebase`, which has verified all blocks, so only the failures to read are unknown.
+impl<const N: usize> RankedResults for Tablebase<N> where RuleSet<N>: Default {
+    fn get_by_rank(&self, rank: u64) -> (GameResult, Option<u32>) {
+        self.probe_rank(rank).map_or((GameResult::Unknown, None), |probe| (probe.game_result, probe.distance))
+    }
+
+    fn get_stored_ranks(&self) -> Vec<u64> {
+        self.index.iter()
+            .flat_map(|(block_number, _)| block_number * RANKS_PER_BLOCK..((block_number + 1) * RANKS_PER_BLOCK).min(get_n_ranks(N)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod create_and_probe {
+        use super::super::{Tablebase, TablebaseError, Probe, ENCODING_VERSION, BLOCK_SIZE, INDEX_ENTRY_SIZE, RANKS_PER_BLOCK};
+        use crate::game::analysis::Analyzer;
+        use crate::game::board3::Board3;
+        use crate::game::commons::{Board, Code, GameResult, Turn};
+        use crate::game::rule_set::RuleSet;
+        use std::path::PathBuf;
+
+        fn create_tablebase(name: &str) -> (PathBuf, Analyzer<Board3>) {
+            let path = std::env::temp_dir().join(format!("sqdr-{}-{}.tb", name, std::process::id()));
+            let analyzer = Analyzer::<Board3>::new();
+            analyzer.analyze(&Board3::decode(&Code("ro3o2f_yf_o3h0tr".to_string())).unwrap());
+
+            Tablebase::create(&path, &analyzer).unwrap();
+
+            (path, analyzer)
+        }
+
+        #[test]
+        fn same_as_analyzer() {
+            let (path, analyzer) = create_tablebase("same");
+            let tablebase = Tablebase::<3>::open(&path).unwrap();
+
+            let (nodes, _) = analyzer.emit_nodes_and_links();
+            for code in nodes.iter().map(|(code, _)| Code(code.clone())) {
+                let board = Board3::decode(&code).unwrap();
+                let probe = tablebase.probe(&board).unwrap();
+
+                assert_eq!(Some((probe.game_result, probe.distance)), analyzer.get_solved(&board.to_key()));
+                assert_eq!(probe.best_move, analyzer.best_move(&code));
+            }
+            assert_eq!(tablebase.probe(&Board3::new(Turn::Red)).unwrap(),
+                       Probe { game_result: GameResult::Unknown, distance: None, best_move: None });
+            assert_eq!(tablebase.probe(&Board3::decode(&Code("ro3o2f_yf_o3h0tr".to_string())).unwrap()).unwrap(),
+                       Probe { game_result: GameResult::YellowWins, distance: Some(8), best_move: analyzer.best_move(&Code("ro3o2f_yf_o3h0tr".to_string())) });
+
+            std::fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn best_moves_and_principal_variation() {
+            let (path, analyzer) = create_tablebase("best");
+            let tablebase = Tablebase::<3>::open(&path).unwrap();
+
+            let (nodes, _) = analyzer.emit_nodes_and_links();
+            for code in nodes.iter().map(|(code, _)| Code(code.clone())) {
+                let board = Board3::decode(&code).unwrap();
+
+                assert_eq!(tablebase.best_moves(&board).unwrap(), analyzer.best_moves(&code));
+                assert_eq!(tablebase.principal_variation(&board).unwrap(), analyzer.principal_variation(&code));
+            }
+            assert!(tablebase.principal_variation(&Board3::new(Turn::Red)).unwrap().is_empty());
+
+            std::fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn other_rules() {
+            let (path, _) = create_tablebase("rules");
+            let tablebase = Tablebase::<3>::open(&path).unwrap();
+
+            assert_eq!(*tablebase.get_rules(), RuleSet::default());
+            assert!(matches!(tablebase.probe(&Board3::with_rules(RuleSet { pieces_to_win: 3, ..RuleSet::default() })),
+                             Err(TablebaseError::Rules)));
+            assert!(matches!(Tablebase::<5>::open(&path), Err(TablebaseError::BoardSize(3))));
+
+            std::fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn only_blocks_of_analyzed_boards() {
+            let (path, analyzer) = create_tablebase("blocks");
+            let tablebase = Tablebase::<3>::open(&path).unwrap();
+
+            let (nodes, _) = analyzer.emit_nodes_and_links();
+            let mut blocks: Vec<_> = nodes.iter()
+                .map(|(code, _)| Board3::decode(&Code(code.clone())).unwrap().to_rank().unwrap() / RANKS_PER_BLOCK)
+                .collect();
+            blocks.sort_unstable();
+            blocks.dedup();
+            assert_eq!(tablebase.index.iter().map(|(n, _)| *n).collect::<Vec<_>>(), blocks);
+            assert_eq!(std::fs::metadata(&path).unwrap().len(), Tablebase::<3>::HEADER_SIZE + blocks.len() as u64 * (BLOCK_SIZE + INDEX_ENTRY_SIZE));
+            assert!(blocks.len() as u64 * RANKS_PER_BLOCK < Board3::get_n_ranks() / 4);
+
+            let mut n_probes = 0;
+            tablebase.for_each_probe(|rank, probe| if probe.game_result != GameResult::Unknown {
+                assert!(analyzer.get_solved(&Board3::from_rank_with_rules(rank, RuleSet::default()).unwrap().to_key()).is_some());
+                n_probes += 1;
+            }).unwrap();
+            assert_eq!(n_probes, nodes.len());
+
+            std::fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn broken_block() {
+            let (path, _) = create_tablebase("block");
+            let mut bytes = std::fs::read(&path).unwrap();
+            let board = Board3::decode(&Code("ro3o2f_yf_o3h0tr".to_string())).unwrap();
+            let tablebase = Tablebase::<3>::open(&path).unwrap();
+            let position = tablebase.index.iter().position(|(n, _)| *n == board.to_rank().unwrap() / RANKS_PER_BLOCK).unwrap();
+            drop(tablebase);
+
+            bytes[Tablebase::<3>::get_block_start(position) as usize] ^= 1;
+            std::fs::write(&path, &bytes).unwrap();
+
+            // only the header and the index are verified when opened
+            let tablebase = Tablebase::<3>::open(&path).unwrap();
+            assert!(matches!(tablebase.probe(&board), Err(TablebaseError::Checksum)));
+            assert!(matches!(tablebase.verify(), Err(TablebaseError::Checksum)));
+            assert_eq!(tablebase.probe(&Board3::new(Turn::Red)).unwrap().game_result, GameResult::Unknown);
+
+            std::fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn broken_files() {
+            let (path, _) = create_tablebase("broken");
+            let bytes = std::fs::read(&path).unwrap();
+            assert!(Tablebase::<3>::open(&path).unwrap().verify().is_ok());
+
+            let mut broken = bytes.clone();
+            *broken.last_mut().unwrap() ^= 1;
+            std::fs::write(&path, &broken).unwrap();
+            assert!(matches!(Tablebase::<3>::open(&path), Err(TablebaseError::Checksum)));
+
+            // the rules, e.g. the speed of the first red piece
+            let mut broken = bytes.clone();
+            broken[12] ^= 1;
+            std::fs::write(&path, &broken).unwrap();
+            assert!(matches!(Tablebase::<3>::open(&path), Err(TablebaseError::Checksum)));
+
+            std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+            assert!(matches!(Tablebase::<3>::open(&path), Err(TablebaseError::Format(_))));
+
+            let mut other_version = bytes.clone();
+            other_version[8..10].copy_from_slice(&(ENCODING_VERSION + 1).to_le_bytes());
+            std::fs::write(&path, &other_version).unwrap();
+            assert!(matches!(Tablebase::<3>::open(&path), Err(TablebaseError::Version(v)) if v == ENCODING_VERSION + 1));
+
+            std::fs::write(&path, b"{}").unwrap();
+            assert!(matches!(Tablebase::<3>::open(&path), Err(TablebaseError::Format(_))));
+
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
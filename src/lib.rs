@@ -0,0 +1,8 @@
+#![allow(clippy::needless_arbitrary_self_type, clippy::needless_return)]
+
+//! The boards of Sqdr (Squadro) and the retrograde analyzer of them.
+//!
+//! `game::commons` (the `Board` trait, codes and keys, results), `game::board3` and `game::analysis` are the stable API.
+//! The other modules of `game` are public for the larger analyses, and may change.
+
+pub mod game;
@@ -1,51 +1,355 @@
-mod game;
+#![allow(clippy::needless_arbitrary_self_type, clippy::needless_return)]
 
-use crate::game::board3::Board3;
-use crate::game::analysis::Analyzer;
-use crate::game::commons::Turn;
+use clap::{Parser, Subcommand, ValueEnum};
+use sqdr_akashic_records::game::board3::Board3;
+use sqdr_akashic_records::game::board5::Board5;
+use sqdr_akashic_records::game::board_n::BoardN;
+use sqdr_akashic_records::game::rule_set::RuleSet;
+use sqdr_akashic_records::game::analysis::{Analyzer, Checkpoint, CheckpointError};
+use sqdr_akashic_records::game::commons::{Board, Code, GameResult, Turn};
+use sqdr_akashic_records::game::play::{run_repl, Game};
+use sqdr_akashic_records::game::protocol::{run_engine, Engine};
+use sqdr_akashic_records::game::server;
+use sqdr_akashic_records::game::tablebase::Tablebase;
+use std::error::Error;
 use std::fs::File;
-use std::io::{LineWriter, Write};
-use std::path::Path;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::Ordering;
 
-fn main() {
-    let board = Board3::new(Turn::Red);
-    let analyzer = Analyzer::new();
-    let result = analyzer.analyze(&board);
-    println!("3x3 Red first -> {}", result);
+// boards between the saves of the checkpoint
+const CHECKPOINT_INTERVAL: usize = 1_000_000;
+
+// exit codes besides 0 and 2 (wrong usage, by clap)
+const EXIT_FAILURE: u8 = 1;
+const EXIT_INTERRUPTED: u8 = 130;
+
+#[derive(Parser)]
+#[command(about = "Solves Squadro by the full search, and queries the results")]
+struct Cli {
+    /// Directory of the results, e.g. the tablebase and the exported graph
+    #[arg(long, short, global = true, default_value = "./results")]
+    output: PathBuf,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Solves the board from the initial boards, and writes the tablebase
+    Solve {
+        #[arg(long, default_value_t = 3)]
+        size: usize,
+        /// Who moves first, both to solve both initial boards
+        #[arg(long, value_enum, default_value_t = First::Both)]
+        first: First,
+    },
+    /// Draws the board of the code with its moves
+    Show {
+        code: String,
+    },
+    /// Looks up the result, the best moves and the best line of the board in the tablebase
+    Query {
+        code: String,
+    },
+    /// Solves the board and writes the graph of all boards and moves
+    Export {
+        #[arg(long, default_value_t = 3)]
+        size: usize,
+        #[arg(long, value_enum)]
+        format: Format,
+    },
+    /// Plays against the solver with the tablebase, with the hints and undo
+    Play {
+        /// Colour of the human player
+        #[arg(long, value_enum, default_value_t = Colour::Red)]
+        colour: Colour,
+        /// Board to start from, instead of the initial board
+        #[arg(long)]
+        code: Option<String>,
+    },
+    /// Answers the engine protocol on stdin and stdout, with the tablebase if solved
+    Engine,
+    /// Answers GET /position/<code> by JSON with the tablebase
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        address: String,
+    },
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum First {
+    Red,
+    Yellow,
+    Both,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Colour {
+    Red,
+    Yellow,
+}
+
+impl Colour {
+    fn to_turn(self: &Self) -> Turn {
+        match self {
+            Colour::Red => Turn::Red,
+            Colour::Yellow => Turn::Yellow,
+        }
+    }
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+    Dot,
+}
+
+type CliResult = Result<(), Box<dyn Error>>;
+
+// stopped by Ctrl-C after saving the checkpoint
+#[derive(Debug)]
+struct Interrupted;
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "interrupted")
+    }
+}
+
+impl Error for Interrupted {}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Command::Solve { size, first } => match size {
+            3 => solve::<3>(&cli.output, first),
+            _ => Err(get_size_error(size)),
+        },
+        Command::Show { code } => show(&code),
+        Command::Query { code } => query(&cli.output, &code),
+        Command::Export { size, format } => match size {
+            3 => export::<3>(&cli.output, format),
+            _ => Err(get_size_error(size)),
+        },
+        Command::Play { colour, code } => play(&cli.output, colour.to_turn(), code.as_deref()),
+        Command::Engine => engine(&cli.output),
+        Command::Serve { address } => serve(&cli.output, &address),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if e.is::<Interrupted>() => ExitCode::from(EXIT_INTERRUPTED),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(EXIT_FAILURE)
+        }
+    }
+}
+
+// only 3x3 fits in memory so far
+fn get_size_error(size: usize) -> Box<dyn Error> {
+    match size {
+        5 => "5x5 is too large to solve in memory so far".into(),
+        _ => format!("no board of size {} (3 or 5)", size).into(),
+    }
+}
+
+fn get_tablebase_path(output: &Path, size: usize) -> PathBuf {
+    output.join(format!("board{}.tb", size))
+}
+
+fn create_output(output: &Path) -> CliResult {
+    std::fs::create_dir_all(output).map_err(|e| format!("failed to create {}: {}", output.display(), e).into())
+}
+
+fn solve<const N: usize>(output: &Path, first: First) -> CliResult where RuleSet<N>: Default {
+    create_output(output)?;
 
-    let board = Board3::new(Turn::Yellow);
-    let result = analyzer.analyze(&board); // reuse "cache"
-    println!("3x3 Yellow first -> {}", result);
+    let checkpoint = Checkpoint::new(&output.join(format!("board{}.checkpoint.json", N)), CHECKPOINT_INTERVAL);
+    let interrupted = checkpoint.interrupted.clone();
+    // the second Ctrl-C quits without waiting for the checkpoint
+    ctrlc::set_handler(move || if interrupted.swap(true, Ordering::SeqCst) { std::process::exit(i32::from(EXIT_INTERRUPTED)); })
+        .map_err(|e| format!("failed to set Ctrl-C handler: {}", e))?;
 
-    println!("Start writing to json file.");
+    let turns = match first {
+        First::Red => vec![Turn::Red],
+        First::Yellow => vec![Turn::Yellow],
+        First::Both => vec![Turn::Red, Turn::Yellow],
+    };
+    let analyzer = if checkpoint.path.exists() {
+        println!("Resume from {}.", checkpoint.path.display());
+        Analyzer::resume(&checkpoint.path, &BoardN::<N>::new(turns[0]))
+            .map_err(|e| format!("failed to resume from {}: {}", checkpoint.path.display(), e))?
+    } else {
+        Analyzer::new()
+    };
 
-    let json_content = analyzer.emit_map_as_json().expect("Invalid structure.");
-    let path = Path::new("./results/board3.json");
+    for turn in turns {
+        let board = BoardN::<N>::new(turn);
+        let result = match analyzer.analyze_with_checkpoint(&board, &checkpoint) { // reuse "cache"
+            Ok(result) => result,
+            Err(CheckpointError::Interrupted) => {
+                println!("Interrupted. Saved to {}, run again to resume.", checkpoint.path.display());
+                return Err(Interrupted.into());
+            }
+            Err(e) => return Err(format!("failed to save checkpoint: {}", e).into()),
+        };
+        println!("{}x{} {:?} first -> {} (distance: {:?})", N, N, turn, result, analyzer.get_distance(&board.encode()));
+    }
+
+    println!("Start writing to tablebase file.");
+
+    let path = get_tablebase_path(output, N);
+    Tablebase::create(&path, &analyzer).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+
+    println!("Wrote {}.", path.display());
+
+    Ok(())
+}
+
+fn show(code: &str) -> CliResult {
+    let code = Code(code.to_string());
+    // the length of the code tells the size
+    match Board3::decode(&code) {
+        Ok(board) => print_board(&board),
+        Err(_) => print_board(&Board5::decode(&code).map_err(|e| format!("invalid code {}: {}", code.0, e))?),
+    }
+
+    Ok(())
+}
+
+fn print_board(board: &impl Board) {
+    println!("{}", board.draw_ascii_art());
+    println!("result: {}", board.get_result());
+    for the_move in board.legal_moves() {
+        println!("{}: {} -> {}", the_move.piece_index, the_move, board.apply_move(&the_move).unwrap().encode().0); // legal move
+    }
+}
+
+fn find_tablebase(output: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let path = get_tablebase_path(output, 3);
+    if !path.exists() {
+        return Err(format!("no tablebase at {}, run `solve` first", path.display()).into());
+    }
 
-    std::fs::write(path, json_content).expect("Failed to write file.");
+    Ok(path)
+}
 
-    println!("Wrote.");
+fn load_tablebase(output: &Path) -> Result<Analyzer<Board3>, Box<dyn Error>> {
+    let path = find_tablebase(output)?;
+    Analyzer::from_tablebase(&path).map_err(|e| format!("failed to load {}: {}", path.display(), e).into())
+}
 
-    println!("Start writing to csv files.");
+fn decode(code: &str) -> Result<Board3, Box<dyn Error>> {
+    Board3::decode(&Code(code.to_string())).map_err(|e| format!("invalid code {}: {}", code, e).into())
+}
 
-    let dump = analyzer.emit_nodes_and_links();
-    let path = File::create("./results/nodes3.csv").expect("Failed to create nodes file.");
-    let mut file = LineWriter::new(path);
+fn query(output: &Path, code: &str) -> CliResult {
+    let board = decode(code)?;
+    let path = find_tablebase(output)?;
+    // only the blocks of the board and its next boards are read
+    let tablebase = Tablebase::<3>::open(&path).map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+    let code = board.encode();
+    let read_error = |e| format!("failed to read {}: {}", path.display(), e);
 
-    for node in dump.0 {
-        writeln!(file, "{},{}", node.0, node.1).expect("Failed to write a line.");
+    let probe = tablebase.probe(&board).map_err(read_error)?;
+    if probe.game_result == GameResult::Unknown {
+        return Err(format!("{} is not reachable from the initial boards", code.0).into());
     }
+    println!("result: {} (distance: {:?})", probe.game_result, probe.distance);
+    for best_move in tablebase.best_moves(&board).map_err(read_error)? {
+        println!("best move: {} -> {} ({}, distance: {:?})", best_move.piece_index, best_move.next_board.0, best_move.game_result, best_move.distance);
+    }
+    let line = tablebase.principal_variation(&board).map_err(read_error)?;
+    println!("principal variation: {}", line.iter().map(|c| c.0.as_str()).collect::<Vec<_>>().join(" "));
+
+    Ok(())
+}
+
+fn export<const N: usize>(output: &Path, format: Format) -> CliResult where RuleSet<N>: Default {
+    create_output(output)?;
 
-    file.flush().expect("Failed to flush nodes file");
+    let analyzer = Analyzer::new();
+    for turn in [Turn::Red, Turn::Yellow].iter() {
+        analyzer.analyze(&BoardN::<N>::new(*turn));
+    }
 
-    let path = File::create("./results/links3.csv").expect("Failed to create links file.");
-    let mut file = LineWriter::new(path);
+    let write = |name: String, f: &dyn Fn(&mut BufWriter<File>) -> std::io::Result<()>| -> CliResult {
+        let path = output.join(&name);
+        let mut file = BufWriter::new(File::create(&path).map_err(|e| format!("failed to create {}: {}", path.display(), e))?);
+        f(&mut file).and_then(|_| file.flush()).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+        println!("Wrote {}.", path.display());
+        Ok(())
+    };
 
-    for link in dump.1 {
-        writeln!(file, "{},{}", link.0, link.1).expect("Failed to write a line.");
+    match format {
+        Format::Json => {
+            let json_content = analyzer.emit_map_as_json()?;
+            write(format!("board{}.json", N), &|file| file.write_all(json_content.as_bytes()))
+        }
+        Format::Csv => {
+            let (nodes, links) = analyzer.emit_nodes_and_links();
+            write(format!("nodes{}.csv", N), &|file| nodes.iter().try_for_each(|node| writeln!(file, "{},{}", node.0, node.1)))?;
+            write(format!("links{}.csv", N), &|file| links.iter().try_for_each(|link| {
+                let jumps = link.3.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(" ");
+                writeln!(file, "{},{},{},{}", link.0, link.1, link.2, jumps)
+            }))
+        }
+        Format::Dot => {
+            let (nodes, links) = analyzer.emit_nodes_and_links();
+            write(format!("board{}.dot", N), &|file| {
+                writeln!(file, "digraph board{} {{", N)?;
+                for (code, result) in nodes.iter() {
+                    let colour = match result.as_str() {
+                        "red" => "red",
+                        "yellow" => "gold",
+                        _ => "gray",
+                    };
+                    writeln!(file, "  \"{}\" [color={}];", code, colour)?;
+                }
+                for (from, to, piece_index, _) in links.iter() {
+                    writeln!(file, "  \"{}\" -> \"{}\" [label={}];", from, to, piece_index)?;
+                }
+                writeln!(file, "}}")
+            })
+        }
     }
+}
+
+fn play(output: &Path, human: Turn, code: Option<&str>) -> CliResult {
+    let board = match code {
+        Some(code) => decode(code)?,
+        None => Board3::new(Turn::Red),
+    };
+    let analyzer = load_tablebase(output)?;
+    let mut game = Game::new(&analyzer, board, human);
+
+    run_repl(&mut game, std::io::stdin().lock(), &mut std::io::stdout())?;
+
+    Ok(())
+}
+
+fn engine(output: &Path) -> CliResult {
+    // without the tablebase, each position is analyzed when asked
+    let analyzer = if get_tablebase_path(output, 3).exists() { load_tablebase(output)? } else { Analyzer::new() };
+    let mut engine = Engine::new(&analyzer, Board3::new(Turn::Red));
+
+    run_engine(&mut engine, std::io::stdin().lock(), &mut std::io::stdout())?;
+
+    Ok(())
+}
+
+fn serve(output: &Path, address: &str) -> CliResult {
+    let analyzer = load_tablebase(output)?;
+    let server = tiny_http::Server::http(address).map_err(|e| format!("failed to listen on {}: {}", address, e))?;
 
-    file.flush().expect("Failed to flush links file");
+    eprintln!("Listening on http://{}/position/<code>.", address);
+    server::serve(&analyzer, &server);
 
-    println!("Wrote.");
-}
\ No newline at end of file
+    Ok(())
+}
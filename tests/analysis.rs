@@ -0,0 +1,58 @@
+use sqdr_akashic_records::game::analysis::Analyzer;
+use sqdr_akashic_records::game::board3::Board3;
+use sqdr_akashic_records::game::commons::{Board, Code, GameResult};
+
+const CODE: &str = "ro3o2f_yf_o3h0tr";
+
+fn analyze() -> (Analyzer<Board3>, Code) {
+    let code = Code(CODE.to_string());
+    let analyzer = Analyzer::new();
+
+    assert_eq!(analyzer.analyze(&Board3::decode(&code).unwrap()), GameResult::YellowWins);
+
+    (analyzer, code)
+}
+
+#[test]
+fn best_line() {
+    let (analyzer, code) = analyze();
+
+    assert_eq!(analyzer.get_distance(&code), Some(8));
+    let best_moves = analyzer.best_moves(&code);
+    assert!(!best_moves.is_empty());
+    assert!(best_moves.iter().all(|m| m.game_result == GameResult::YellowWins && m.distance == Some(8)));
+    assert_eq!(analyzer.best_move(&code), Some(best_moves[0].piece_index));
+
+    let line = analyzer.principal_variation(&code);
+    assert_eq!(line.len(), 9);
+    assert_eq!(line[0], code);
+    assert_eq!(Board3::decode(line.last().unwrap()).unwrap().get_result(), GameResult::YellowWins);
+    for (board, next_board) in line.iter().zip(line.iter().skip(1)) {
+        let board = Board3::decode(board).unwrap();
+        assert!(board.legal_moves().iter().any(|m| board.apply_move(m).unwrap().encode() == *next_board));
+    }
+}
+
+#[test]
+fn exported_graph() {
+    let (analyzer, code) = analyze();
+    let (nodes, links) = analyzer.emit_nodes_and_links();
+
+    assert_eq!(analyzer.get_graph().len(), nodes.len());
+    assert!(nodes.iter().any(|(c, result)| *c == code.0 && result == "yellow"));
+    assert!(links.iter().all(|(from, to, _, _)| nodes.iter().any(|(c, _)| c == from) && nodes.iter().any(|(c, _)| c == to)));
+
+    let loaded = Analyzer::<Board3>::from_json(&analyzer.emit_map_as_json().unwrap()).unwrap();
+    assert_eq!(loaded.get_distance(&code), Some(8));
+    assert_eq!(loaded.best_moves(&code), analyzer.best_moves(&code));
+}
+
+#[test]
+fn not_analyzed() {
+    let analyzer = Analyzer::<Board3>::new();
+    let code = Code(CODE.to_string());
+
+    assert_eq!(analyzer.get_distance(&code), None);
+    assert!(analyzer.best_moves(&code).is_empty());
+    assert!(analyzer.principal_variation(&code).is_empty());
+}
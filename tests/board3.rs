@@ -0,0 +1,28 @@
+use sqdr_akashic_records::game::board3::Board3;
+use sqdr_akashic_records::game::commons::{Board, Code, GameResult, Turn};
+
+#[test]
+fn code_and_key_round_trip() {
+    let code = Code("ro3o2f_yf_o3h0tr".to_string());
+    let board = Board3::decode(&code).unwrap();
+
+    assert_eq!(board.encode(), code);
+    assert_eq!(Board3::from_key(&board.to_key()).unwrap().encode(), code);
+    assert_eq!(code.get_turn::<Board3>(), Turn::Red);
+    assert!(Board3::decode(&Code("invalid".to_string())).is_err());
+}
+
+#[test]
+fn next_boards() {
+    let board = Board3::new(Turn::Red);
+    let moves = board.legal_moves();
+
+    assert_eq!(moves.len(), 3);
+    for the_move in moves.iter() {
+        let next_board = board.apply_move(the_move).unwrap();
+        assert_eq!(next_board.encode().get_turn::<Board3>(), Turn::Yellow);
+        assert_eq!(next_board.get_result(), GameResult::Unknown);
+        assert_eq!(board.move_at(the_move.piece_index).unwrap().encode(), next_board.encode());
+    }
+    assert!(!board.draw_ascii_art().is_empty());
+}
@@ -0,0 +1,38 @@
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sqdr-akashic-records")).args(args).output().unwrap()
+}
+
+#[test]
+fn show() {
+    let output = run(&["show", "ro0o0o0yo0o0o0tr"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("next-> Red"));
+    assert!(stdout.contains("0: r0:o0-o2 -> ro2o0o0yo0o0o0ty"));
+}
+
+#[test]
+fn errors() {
+    let directory = std::env::temp_dir().join(format!("sqdr-cli-{}", std::process::id()));
+    let directory = directory.to_str().unwrap();
+
+    let output = run(&["show", "invalid"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("error: invalid code invalid"));
+
+    let output = run(&["query", "ro0o0o0yo0o0o0tr", "--output", directory]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("run `solve` first"));
+
+    let output = run(&["solve", "--size", "5", "--output", directory]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("5x5 is too large"));
+    let output = run(&["export", "--size", "4", "--format", "csv", "--output", directory]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("no board of size 4"));
+    assert_eq!(run(&["export", "--format", "xml"]).status.code(), Some(2));
+    assert_eq!(run(&[]).status.code(), Some(2));
+}
@@ -0,0 +1,48 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+// yellow wins in 8 plies with red to move
+const CODE: &str = "ro3o2f_yf_o3h0tr";
+
+#[test]
+fn through_pipes() {
+    // no tablebase, so the positions are analyzed by the engine
+    let directory = std::env::temp_dir().join(format!("sqdr-engine-{}", std::process::id()));
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sqdr-akashic-records"))
+        .args(["engine", "--output", directory.to_str().unwrap()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    // each command is answered before the next one is sent
+    let mut ask = |command: &str, n_lines: usize| -> Vec<String> {
+        writeln!(stdin, "{}", command).unwrap();
+        stdin.flush().unwrap();
+        (0..n_lines).map(|_| {
+            let mut line = String::new();
+            stdout.read_line(&mut line).unwrap();
+            line.trim_end().to_string()
+        }).collect()
+    };
+
+    assert_eq!(ask("isready", 1), vec!["readyok"]);
+    assert_eq!(ask(&format!("position {}", CODE), 0), Vec::<String>::new());
+    assert_eq!(ask("result", 1), vec!["result yellow distance 8"]);
+
+    let go = ask("go", 2);
+    assert_eq!(go[0], "info result yellow distance 8");
+    let best_move = go[1].strip_prefix("bestmove ").unwrap();
+    assert_eq!(ask("bestmove", 1), vec![format!("bestmove {}", best_move)]);
+
+    assert_eq!(ask(&format!("position {} moves {}", CODE, best_move), 0), Vec::<String>::new());
+    assert_eq!(ask("result", 1), vec!["result yellow distance 7"]);
+    assert_eq!(ask("position startpos moves 7", 1), vec!["error illegal move 7"]);
+    assert_eq!(ask("board", 1)[0].split(' ').nth(1).unwrap().len(), CODE.len());
+
+    writeln!(stdin, "quit").unwrap();
+    assert!(child.wait().unwrap().success());
+}